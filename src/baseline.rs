@@ -0,0 +1,123 @@
+//! Findings recorded with `--write-baseline`. With `--baseline`, findings already
+//! in the file are reported but do not fail the run, so the tool can be turned on
+//! in a repository before every workload passes.
+
+use crate::checker::CheckResult;
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::Path;
+
+const VERSION: u32 = 1;
+
+/// Identifies a failure or warning across runs.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Fingerprint {
+    pub environment: String,
+    /// Kind of the workload, telling apart workloads of different kinds with one name.
+    pub kind: String,
+    /// `namespace/name` of the workload.
+    pub workload: String,
+    /// Container that sets the variable; `None` when no container does.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub var: Option<String>,
+    /// ID of the check that reported the finding.
+    pub rule: String,
+    /// Set for invalid values and rule violations, telling apart several
+    /// findings of one check on the same variable or workload.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} [{}] {}", self.kind, self.workload, self.environment, self.rule)?;
+        if let Some(var) = &self.var {
+            write!(f, " {}", var)?;
+        }
+        if let Some(container) = &self.container {
+            write!(f, " in container {}", container)?;
+        }
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+/// A set of known findings, stored as JSON.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Baseline {
+    version: u32,
+    findings: BTreeSet<Fingerprint>,
+}
+
+impl Baseline {
+    /// Records every failure and warning in `results`, including ones that
+    /// matched an earlier baseline.
+    pub fn from_results(results: &[CheckResult]) -> Self {
+        Self {
+            version: VERSION,
+            findings: results.iter().flat_map(|r| r.findings.iter().cloned()).collect(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let baseline: Self = serde_json::from_str(&content).map_err(|source| Error::Baseline {
+            path: path.to_path_buf(),
+            message: source.to_string(),
+        })?;
+        if baseline.version != VERSION {
+            return Err(Error::Baseline {
+                path: path.to_path_buf(),
+                message: format!("unsupported version {}; expected {}", baseline.version, VERSION),
+            });
+        }
+        Ok(baseline)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut content = serde_json::to_string_pretty(self)?;
+        content.push('\n');
+        std::fs::write(path, content).map_err(|source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    pub fn contains(&self, fingerprint: &Fingerprint) -> bool {
+        self.findings.contains(fingerprint)
+    }
+
+    pub fn len(&self) -> usize {
+        self.findings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Recorded findings of `checks` for the environments in `results` that no
+    /// longer occur, and can be dropped by writing the baseline again. Findings of
+    /// checks that did not run, or that were silenced, are not fixed.
+    pub fn fixed(&self, results: &[CheckResult], checks: &[&str]) -> Vec<Fingerprint> {
+        let environments: BTreeSet<&str> = results.iter().map(|r| r.environment.as_str()).collect();
+        let current: BTreeSet<&Fingerprint> = results.iter().flat_map(|r| r.findings.iter().chain(&r.silenced)).collect();
+        self.findings
+            .iter()
+            .filter(|f| {
+                environments.contains(f.environment.as_str()) && checks.contains(&f.rule.as_str()) && !current.contains(f)
+            })
+            .cloned()
+            .collect()
+    }
+}
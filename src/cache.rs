@@ -0,0 +1,290 @@
+use crate::error::{Error, Result};
+use anyhow::Context;
+use serde_yaml::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const KUSTOMIZATION_FILES: &[&str] = &["kustomization.yaml", "kustomization.yml", "Kustomization"];
+
+/// On-disk cache of rendered `kustomize build` output, keyed by a hash of every
+/// local file the overlay transitively references.
+#[derive(Debug, Clone)]
+pub struct BuildCache {
+    dir: PathBuf,
+    salt: String,
+}
+
+impl BuildCache {
+    /// `salt` is mixed into every key, so entries rendered by a different
+    /// kustomize version are never reused.
+    pub fn new(dir: PathBuf, salt: impl Into<String>) -> Self {
+        Self { dir, salt: salt.into() }
+    }
+
+    pub fn default_dir() -> PathBuf {
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .unwrap_or_else(std::env::temp_dir);
+        base.join("kustomize-envcheck")
+    }
+
+    /// Returns the cache key for an overlay, or `None` when the overlay cannot be
+    /// cached (remote bases, helm charts, or references that cannot be read).
+    pub fn key(&self, overlay: &Path) -> Option<String> {
+        let mut files = BTreeSet::new();
+        if !collect_kustomization(overlay, &mut files, &mut BTreeSet::new()).ok()? {
+            return None;
+        }
+
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt.as_bytes());
+        hasher.update([0]);
+        hasher.update(overlay.canonicalize().ok()?.to_string_lossy().as_bytes());
+        for file in &files {
+            let content = std::fs::read(file).ok()?;
+            hasher.update([0]);
+            hasher.update(file.to_string_lossy().as_bytes());
+            hasher.update([0]);
+            hasher.update((content.len() as u64).to_le_bytes());
+            hasher.update(&content);
+        }
+
+        Some(format!("{:x}", hasher.finalize()))
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        std::fs::read_to_string(self.entry_path(key)).ok()
+    }
+
+    pub fn put(&self, key: &str, content: &str) -> Result<()> {
+        std::fs::create_dir_all(&self.dir).map_err(|source| Error::Io { path: self.dir.clone(), source })?;
+
+        let path = self.entry_path(key);
+        let tmp = path.with_extension(format!("yaml.tmp-{}", std::process::id()));
+        std::fs::write(&tmp, content).map_err(|source| Error::Io { path: tmp.clone(), source })?;
+        std::fs::rename(&tmp, &path).map_err(|source| Error::Io { path: path.clone(), source })?;
+
+        Ok(())
+    }
+
+    /// Removes cache entries older than `max_age`, or every entry when `max_age`
+    /// is `None`. Returns the number of entries removed.
+    pub fn prune(dir: &Path, max_age: Option<Duration>) -> Result<usize> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(source) => return Err(Error::Io { path: dir.to_path_buf(), source }),
+        };
+        let io_error = |path: &Path| {
+            let path = path.to_path_buf();
+            move |source| Error::Io { path, source }
+        };
+
+        let now = SystemTime::now();
+        let mut removed = 0;
+        for entry in entries {
+            let entry = entry.map_err(io_error(dir))?;
+            let path = entry.path();
+            let is_entry = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.ends_with(".yaml") || n.contains(".yaml.tmp-"));
+            if !is_entry {
+                continue;
+            }
+
+            if let Some(max_age) = max_age {
+                let modified = entry.metadata().and_then(|m| m.modified()).map_err(io_error(&path))?;
+                if now.duration_since(modified).unwrap_or_default() < max_age {
+                    continue;
+                }
+            }
+
+            std::fs::remove_file(&path).map_err(io_error(&path))?;
+            removed += 1;
+        }
+
+        Ok(removed)
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.yaml", key))
+    }
+}
+
+/// Adds the kustomization file of `dir` and everything it references to `files`.
+/// Returns `Ok(false)` if the kustomization pulls in anything that is not a local
+/// file, since such overlays cannot be cached safely.
+fn collect_kustomization(
+    dir: &Path,
+    files: &mut BTreeSet<PathBuf>,
+    visited: &mut BTreeSet<PathBuf>,
+) -> anyhow::Result<bool> {
+    let dir = dir.canonicalize()?;
+    if !visited.insert(dir.clone()) {
+        return Ok(true);
+    }
+
+    let kustomization = KUSTOMIZATION_FILES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+        .with_context(|| format!("No kustomization file in {}", dir.display()))?;
+    let value: Value = serde_yaml::from_str(&std::fs::read_to_string(&kustomization)?)?;
+    files.insert(kustomization);
+
+    if value.get("helmCharts").is_some() {
+        return Ok(false);
+    }
+
+    let mut references = Vec::new();
+    for key in [
+        "resources",
+        "bases",
+        "components",
+        "crds",
+        "configurations",
+        "generators",
+        "transformers",
+        "validators",
+        "patchesStrategicMerge",
+    ] {
+        references.extend(string_list(value.get(key)));
+    }
+    for key in ["patches", "patchesJson6902", "replacements"] {
+        if let Some(items) = value.get(key).and_then(|v| v.as_sequence()) {
+            references.extend(
+                items
+                    .iter()
+                    .filter_map(|item| item.get("path").and_then(|p| p.as_str()))
+                    .map(str::to_string),
+            );
+        }
+    }
+    for key in ["configMapGenerator", "secretGenerator"] {
+        if let Some(generators) = value.get(key).and_then(|v| v.as_sequence()) {
+            for generator in generators {
+                for file in string_list(generator.get("files")) {
+                    // `files` entries may be written as `key=path`.
+                    let path = file.split_once('=').map_or(file.as_str(), |(_, path)| path);
+                    references.push(path.to_string());
+                }
+                references.extend(string_list(generator.get("envs")));
+                references.extend(string_list(generator.get("env")));
+            }
+        }
+    }
+    if let Some(openapi) = value.get("openapi").and_then(|v| v.get("path")).and_then(|p| p.as_str()) {
+        references.push(openapi.to_string());
+    }
+
+    for reference in references {
+        // Inline patches are YAML documents rather than paths; they are already
+        // covered by hashing the kustomization file itself.
+        if reference.contains('\n') {
+            continue;
+        }
+        if is_remote(&reference) {
+            return Ok(false);
+        }
+
+        let path = dir.join(&reference);
+        if path.is_dir() {
+            if !collect_kustomization(&path, files, visited)? {
+                return Ok(false);
+            }
+        } else {
+            files.insert(path.canonicalize()?);
+        }
+    }
+
+    Ok(true)
+}
+
+fn string_list(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::Sequence(items)) => items.iter().filter_map(|v| v.as_str()).map(str::to_string).collect(),
+        Some(Value::String(s)) => vec![s.clone()],
+        _ => Vec::new(),
+    }
+}
+
+fn is_remote(reference: &str) -> bool {
+    reference.contains("://")
+        || reference.starts_with("git@")
+        || reference.starts_with("github.com/")
+        || reference.starts_with("gitlab.com/")
+        || reference.starts_with("bitbucket.org/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Writes `files` into a temporary directory, removed when it is dropped.
+    fn fixture(files: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn cache(dir: &TempDir) -> BuildCache {
+        BuildCache::new(dir.path().join("cache"), "v5.4.3")
+    }
+
+    #[test]
+    fn key_changes_with_referenced_patch() {
+        let dir = fixture(&[
+            ("base/kustomization.yaml", "resources: [deployment.yaml]\n"),
+            ("base/deployment.yaml", "kind: Deployment\n"),
+            ("overlay/kustomization.yaml", "resources: [../base]\npatches:\n  - path: patch.yaml\n"),
+            ("overlay/patch.yaml", "kind: Deployment\n"),
+        ]);
+        let overlay = dir.path().join("overlay");
+        let key = cache(&dir).key(&overlay).unwrap();
+        assert_eq!(cache(&dir).key(&overlay), Some(key.clone()));
+
+        std::fs::write(overlay.join("patch.yaml"), "kind: Deployment\nspec: {}\n").unwrap();
+        let patched = cache(&dir).key(&overlay).unwrap();
+        assert_ne!(patched, key);
+
+        std::fs::write(dir.path().join("base/deployment.yaml"), "kind: StatefulSet\n").unwrap();
+        assert_ne!(cache(&dir).key(&overlay).unwrap(), patched);
+
+        assert_ne!(BuildCache::new(dir.path().join("cache"), "v5.5.0").key(&overlay).unwrap(), patched);
+    }
+
+    #[test]
+    fn remote_bases_and_helm_charts_are_not_cached() {
+        let dir = fixture(&[
+            ("remote/kustomization.yaml", "resources:\n  - https://github.com/org/repo//base?ref=v1\n"),
+            ("nested/kustomization.yaml", "resources: [../remote]\n"),
+            ("helm/kustomization.yaml", "helmCharts:\n  - name: redis\n    repo: https://charts.example.com\n"),
+        ]);
+        for overlay in ["remote", "nested", "helm"] {
+            assert_eq!(cache(&dir).key(&dir.path().join(overlay)), None, "{}", overlay);
+        }
+    }
+
+    #[test]
+    fn entries_round_trip_and_prune() {
+        let dir = fixture(&[]);
+        let cache = cache(&dir);
+        assert_eq!(cache.get("abc"), None);
+        cache.put("abc", "kind: Deployment\n").unwrap();
+        assert_eq!(cache.get("abc").as_deref(), Some("kind: Deployment\n"));
+
+        assert_eq!(BuildCache::prune(&dir.path().join("cache"), Some(Duration::from_secs(3600))).unwrap(), 0);
+        assert_eq!(BuildCache::prune(&dir.path().join("cache"), None).unwrap(), 1);
+        assert_eq!(cache.get("abc"), None);
+    }
+}
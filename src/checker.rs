@@ -1,115 +1,408 @@
-use crate::config::Config;
-use crate::k8s::{K8sResource, ContainerInfo};
-use anyhow::Result;
+use crate::baseline::{Baseline, Fingerprint};
+use crate::config::{glob_to_regex, Application, Config, IdentitySource, Severity};
+use crate::k8s::{K8sResource, ContainerInfo, Origin, Suppressions, IGNORE_ANNOTATION, SKIP_ANNOTATION};
+use crate::rules::{Finding, Rule, RuleContext, RuleRegistry};
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
+/// Outcome of checking one workload.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct CheckResult {
     pub app_name: String,
+    pub namespace: Option<String>,
+    /// Entry under `applications` the workload was matched to, if any.
+    pub application: Option<String>,
     pub status: CheckStatus,
     pub missing_required: Vec<String>,
     pub missing_optional: Vec<String>,
     pub using_defaults: Vec<String>,
     pub extra_vars: Vec<String>,
     pub validation_errors: Vec<ValidationError>,
+    /// Messages for `rules` that do not hold.
+    pub rule_violations: Vec<RuleViolation>,
     pub passed_vars: Vec<(String, String)>, // (name, value)
+    pub var_origins: HashMap<String, Origin>,
+    /// Findings the workload's annotations suppressed; they do not affect `status`.
+    pub suppressed: Vec<SuppressedFinding>,
+    /// Environment the workload was checked against.
+    pub environment: String,
+    /// Every failure and warning that was not suppressed, including those in the baseline.
+    pub findings: Vec<Fingerprint>,
+    /// Findings already in the baseline; they do not affect `status`.
+    pub baselined: Vec<Fingerprint>,
+    /// Findings dropped by an `off` severity or suppressed by annotations; a
+    /// baseline does not count them as fixed.
+    pub silenced: Vec<Fingerprint>,
+    /// Configured severity of missing variables where it differs from the default
+    /// for their kind, keyed by variable name.
+    pub severities: HashMap<String, Severity>,
 }
 
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum CheckStatus {
     Passed,
     Failed,
     Warning,
 }
 
+/// A variable whose value fails its `pattern` or a rule expression.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct ValidationError {
     pub var_name: String,
     pub message: String,
+    pub severity: Severity,
 }
 
+/// A configured rule, or another check not tied to one variable, that does not hold.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct RuleViolation {
+    pub message: String,
+    pub severity: Severity,
+}
+
+/// A failure or warning suppressed by an `envcheck.io/ignore` or `envcheck.io/skip` annotation.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SuppressedFinding {
+    /// Variable the finding is about; `None` for rule violations.
+    pub var_name: Option<String>,
+    pub message: String,
+    /// Annotation that suppressed the finding.
+    pub annotation: &'static str,
+}
+
+/// A configured application with no workload, or a workload with no application.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct CoverageFinding {
+    pub kind: CoverageKind,
+    /// Application name for `MissingApplication`, `namespace/name` for `UnconfiguredWorkload`.
+    pub name: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CoverageKind {
+    MissingApplication,
+    UnconfiguredWorkload,
+}
+
+/// Checks workloads against a [`Config`].
 pub struct EnvChecker {
     config: Config,
+    rules: RuleRegistry,
+    allow_suppressions: bool,
+    baseline: Baseline,
+    /// Every application `name_regex`, anchored and compiled once up front.
+    name_regexes: HashMap<String, Regex>,
 }
 
 impl EnvChecker {
+    /// A checker running the built-in checks.
     pub fn new(config: Config) -> Self {
-        Self { config }
+        let rules = RuleRegistry::builtin(&config);
+        let name_regexes = config
+            .applications
+            .values()
+            .filter_map(|app| app.selector.as_ref()?.name_regex.as_ref())
+            .filter_map(|pattern| Some((pattern.clone(), Regex::new(&format!("^(?:{})$", pattern)).ok()?)))
+            .collect();
+        Self {
+            config,
+            rules,
+            allow_suppressions: true,
+            baseline: Baseline::default(),
+            name_regexes,
+        }
     }
 
-    pub fn check_resources(&self, resources: &[K8sResource], environment: Option<&str>) -> Vec<CheckResult> {
+    /// Replaces the checks to run, e.g. with a registry that includes plugins.
+    pub fn with_rules(mut self, rules: RuleRegistry) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Whether workloads may suppress findings with annotations at all; when
+    /// allowed, an environment can still forbid it with `allow_suppressions: false`.
+    pub fn with_suppressions_allowed(mut self, allowed: bool) -> Self {
+        self.allow_suppressions = allowed;
+        self
+    }
+
+    /// Findings in `baseline` are listed in [`CheckResult::baselined`] instead of failing.
+    pub fn with_baseline(mut self, baseline: Baseline) -> Self {
+        self.baseline = baseline;
+        self
+    }
+
+    pub fn baseline(&self) -> &Baseline {
+        &self.baseline
+    }
+
+    /// Baseline entries that no longer occur in `results`, among the checks this
+    /// checker runs; see [`Baseline::fixed`].
+    pub fn fixed(&self, results: &[CheckResult]) -> Vec<Fingerprint> {
+        let checks: Vec<&str> = self.rules.enabled().map(|rule| rule.id()).collect();
+        self.baseline.fixed(results, &checks)
+    }
+
+    pub fn rules(&self) -> &RuleRegistry {
+        &self.rules
+    }
+
+    /// For registering custom checks and enabling or disabling checks by ID.
+    pub fn rules_mut(&mut self) -> &mut RuleRegistry {
+        &mut self.rules
+    }
+
+    pub fn check_resources(&self, resources: &[K8sResource], env_name: &str) -> Vec<CheckResult> {
         resources
             .iter()
-            .map(|resource| self.check_resource(resource, environment))
+            .map(|resource| self.check_resource(resource, env_name))
             .collect()
     }
 
-    fn check_resource(&self, resource: &K8sResource, environment: Option<&str>) -> CheckResult {
-        let env_name = environment.unwrap_or("development");
+    /// Reports configured applications for the environment that match no rendered
+    /// workload, and rendered workloads that match no configured application.
+    pub fn check_coverage(&self, resources: &[K8sResource], env_name: &str) -> Vec<CoverageFinding> {
+        let coverage = &self.config.coverage;
+        let mut findings = Vec::new();
+
+        if coverage.missing_applications != Severity::Off {
+            let mut missing: Vec<&String> = self
+                .config
+                .applications
+                .iter()
+                .filter(|(_, app)| app.environments.iter().any(|e| e == env_name))
+                .filter(|(name, app)| !resources.iter().any(|r| self.matches_application(name, app, r)))
+                .map(|(name, _)| name)
+                .collect();
+            missing.sort();
+
+            findings.extend(missing.into_iter().map(|name| CoverageFinding {
+                kind: CoverageKind::MissingApplication,
+                name: name.clone(),
+                severity: coverage.missing_applications,
+                message: format!(
+                    "Application '{}' is configured for {} but matches no rendered workload",
+                    name, env_name
+                ),
+            }));
+        }
+
+        if coverage.unconfigured_workloads != Severity::Off {
+            for resource in resources.iter().filter(|r| self.application_for(r).is_none()) {
+                let name = format!("{}/{}", resource.effective_namespace(), resource.name);
+                findings.push(CoverageFinding {
+                    kind: CoverageKind::UnconfiguredWorkload,
+                    message: format!("{} '{}' has no application entry", resource.kind, name),
+                    name,
+                    severity: coverage.unconfigured_workloads,
+                });
+            }
+        }
+
+        findings
+    }
+
+    fn application_for(&self, resource: &K8sResource) -> Option<&str> {
+        let mut names: Vec<&String> = self.config.applications.keys().collect();
+        names.sort();
+
+        names
+            .into_iter()
+            .find(|name| self.matches_application(name, &self.config.applications[*name], resource))
+            .map(String::as_str)
+    }
+
+    fn matches_application(&self, app_name: &str, app: &Application, resource: &K8sResource) -> bool {
+        let Some(selector) = app.selector.as_ref().filter(|s| !s.is_empty()) else {
+            let identity = &self.config.app_identity;
+            return match identity.by {
+                IdentitySource::Name => resource.name == app_name,
+                IdentitySource::StrippedName => resource.base_name == app_name,
+                IdentitySource::Label => resource.labels.get(&identity.key).is_some_and(|v| v == app_name),
+                IdentitySource::Annotation => resource.annotations.get(&identity.key).is_some_and(|v| v == app_name),
+            };
+        };
+
+        let expected = selector.value.as_deref().unwrap_or(app_name);
+        if let Some(key) = &selector.label {
+            if resource.labels.get(key).map(String::as_str) != Some(expected) {
+                return false;
+            }
+        }
+        if let Some(key) = &selector.annotation {
+            if resource.annotations.get(key).map(String::as_str) != Some(expected) {
+                return false;
+            }
+        }
+        if let Some(pattern) = &selector.name_regex {
+            // Config::from_path rejects invalid patterns, so only a configuration that
+            // was never validated misses here, and its selector matches nothing.
+            if !self.name_regexes.get(pattern).is_some_and(|regex| regex.is_match(&resource.name)) {
+                return false;
+            }
+        }
+        if let Some(glob) = &selector.name_glob {
+            if !glob_to_regex(glob).is_match(&resource.name) {
+                return false;
+            }
+        }
+        if let Some(glob) = &selector.namespace {
+            if !glob_to_regex(glob).is_match(resource.effective_namespace()) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn check_resource(&self, resource: &K8sResource, env_name: &str) -> CheckResult {
+        let application = self.application_for(resource);
         let mut result = CheckResult {
             app_name: resource.name.clone(),
+            namespace: resource.namespace.clone(),
+            application: application.map(str::to_string),
             status: CheckStatus::Passed,
             missing_required: Vec::new(),
             missing_optional: Vec::new(),
             using_defaults: Vec::new(),
             extra_vars: Vec::new(),
             validation_errors: Vec::new(),
+            rule_violations: Vec::new(),
             passed_vars: Vec::new(),
+            var_origins: self.collect_all_env_origins(&resource.containers),
+            suppressed: Vec::new(),
+            environment: env_name.to_string(),
+            findings: Vec::new(),
+            baselined: Vec::new(),
+            silenced: Vec::new(),
+            severities: HashMap::new(),
         };
 
         let all_env_vars = self.collect_all_env_vars(&resource.containers);
-        let required_vars = self.config.get_required_vars(&resource.name, env_name);
-        let optional_vars = self.config.get_optional_vars(&resource.name, env_name);
-
-        for var in &required_vars {
-            if !all_env_vars.contains_key(&var.name) {
-                result.missing_required.push(var.name.clone());
-                result.status = CheckStatus::Failed;
-            } else if let Some(value) = all_env_vars.get(&var.name) {
-                if let Some(pattern) = &var.pattern {
-                    if let Err(e) = self.validate_pattern(&var.name, value, pattern) {
-                        result.validation_errors.push(e);
-                        result.status = CheckStatus::Failed;
-                    } else {
-                        result.passed_vars.push((var.name.clone(), value.clone()));
-                    }
-                } else {
-                    result.passed_vars.push((var.name.clone(), value.clone()));
+        let namespace = resource.effective_namespace();
+        let required_vars: Vec<_> = self
+            .config
+            .get_required_vars(application, env_name)
+            .into_iter()
+            .filter(|v| v.applies_to_namespace(namespace))
+            .collect();
+        let optional_vars: Vec<_> = self
+            .config
+            .get_optional_vars(application, env_name)
+            .into_iter()
+            .filter(|v| v.applies_to_namespace(namespace))
+            .collect();
+        let context = RuleContext {
+            resource,
+            environment: env_name,
+            application,
+            config: &self.config,
+            vars: &all_env_vars,
+            required_vars: &required_vars,
+            optional_vars: &optional_vars,
+        };
+
+        let no_suppressions = Suppressions::default();
+        let suppressions = if self.allow_suppressions && self.config.allows_suppressions(env_name) {
+            &resource.suppressions
+        } else {
+            &no_suppressions
+        };
+
+        let mut passed = Vec::new();
+        let findings = self
+            .rules
+            .enabled()
+            .flat_map(|rule| rule.check(&context).into_iter().map(move |finding| (rule, finding)));
+        for (rule, finding) in findings {
+            let severity = self.severity(&finding, rule, &context);
+            let fingerprint = fingerprint(&finding, rule.id(), resource, env_name);
+            if severity == Some(Severity::Off) {
+                result.silenced.extend(fingerprint);
+                continue;
+            }
+            let finding = match suppress(finding, suppressions) {
+                Ok(finding) => finding,
+                Err(suppressed) => {
+                    result.suppressed.push(suppressed);
+                    result.silenced.extend(fingerprint);
+                    continue;
+                }
+            };
+            if let Some(fingerprint) = fingerprint {
+                result.findings.push(fingerprint.clone());
+                if self.baseline.contains(&fingerprint) {
+                    result.baselined.push(fingerprint);
+                    continue;
                 }
             }
-        }
-
-        for var in &optional_vars {
-            if !all_env_vars.contains_key(&var.name) {
-                if var.default.is_some() {
-                    result.using_defaults.push(var.name.clone());
-                } else {
-                    result.missing_optional.push(var.name.clone());
-                    if matches!(result.status, CheckStatus::Passed) {
-                        result.status = CheckStatus::Warning;
+            if let Some(severity) = severity {
+                match &finding {
+                    Finding::MissingRequired(name) if severity != Severity::Error => {
+                        result.severities.insert(name.clone(), severity);
+                    }
+                    Finding::MissingOptional(name) if severity != Severity::Warning => {
+                        result.severities.insert(name.clone(), severity);
                     }
+                    _ => {}
+                }
+                match severity {
+                    Severity::Error => result.status = CheckStatus::Failed,
+                    Severity::Warning if matches!(result.status, CheckStatus::Passed) => {
+                        result.status = CheckStatus::Warning
+                    }
+                    _ => {}
                 }
-            } else if let Some(value) = all_env_vars.get(&var.name) {
-                result.passed_vars.push((var.name.clone(), value.clone()));
+            }
+            match finding {
+                Finding::MissingRequired(name) => result.missing_required.push(name),
+                Finding::MissingOptional(name) => result.missing_optional.push(name),
+                Finding::UsingDefault(name) => result.using_defaults.push(name),
+                Finding::Extra(name) => result.extra_vars.push(name),
+                Finding::Passed { var_name, value } => passed.push((var_name, value)),
+                Finding::Invalid { var_name, message } => result.validation_errors.push(ValidationError {
+                    var_name,
+                    message,
+                    severity: severity.unwrap_or(Severity::Error),
+                }),
+                Finding::Violation(message) => result.rule_violations.push(RuleViolation {
+                    message,
+                    severity: severity.unwrap_or(Severity::Error),
+                }),
             }
         }
-
-        let expected_vars: HashSet<String> = required_vars
-            .iter()
-            .chain(optional_vars.iter())
-            .map(|v| v.name.clone())
-            .collect();
-
-        for var_name in all_env_vars.keys() {
-            if !expected_vars.contains(var_name) {
-                result.extra_vars.push(var_name.clone());
+        // A variable only passes if no check found its value invalid.
+        for (name, value) in passed {
+            let invalid = result.validation_errors.iter().any(|e| e.var_name == name);
+            if !invalid && !result.passed_vars.iter().any(|(passed, _)| *passed == name) {
+                result.passed_vars.push((name, value));
             }
         }
 
         result
     }
 
+    /// Severity of a failure or warning: the variable's if configured, else the
+    /// check's, else the check's own default, else the default for its kind.
+    /// `None` for other findings.
+    fn severity(&self, finding: &Finding, rule: &dyn Rule, context: &RuleContext<'_>) -> Option<Severity> {
+        let (var_name, default) = failure_entry(finding)?;
+        let var = var_name.and_then(|name| context.required_vars.iter().chain(context.optional_vars).find(|v| v.name == *name));
+        var.and_then(|v| v.severity)
+            .or_else(|| self.config.severity.get(rule.id()).copied())
+            .or_else(|| rule.default_severity())
+            .or(Some(default))
+    }
+
     fn collect_all_env_vars(&self, containers: &[ContainerInfo]) -> HashMap<String, String> {
         let mut all_vars = HashMap::new();
 
@@ -122,19 +415,278 @@ impl EnvChecker {
         all_vars
     }
 
-    fn validate_pattern(&self, var_name: &str, value: &str, pattern: &str) -> Result<(), ValidationError> {
-        let regex = Regex::new(pattern).map_err(|e| ValidationError {
-            var_name: var_name.to_string(),
-            message: format!("Invalid regex pattern: {}", e),
-        })?;
+    fn collect_all_env_origins(&self, containers: &[ContainerInfo]) -> HashMap<String, Origin> {
+        let mut all_origins = HashMap::new();
+
+        for container in containers {
+            for (key, origin) in &container.env_origins {
+                all_origins.insert(key.clone(), origin.clone());
+            }
+        }
+
+        all_origins
+    }
+}
+
+/// The variable a failure or warning is about, if any, and the default severity
+/// of its kind.
+fn failure_entry(finding: &Finding) -> Option<(Option<&String>, Severity)> {
+    match finding {
+        Finding::MissingRequired(name) | Finding::Invalid { var_name: name, .. } => Some((Some(name), Severity::Error)),
+        Finding::MissingOptional(name) => Some((Some(name), Severity::Warning)),
+        Finding::Violation(_) => Some((None, Severity::Error)),
+        _ => None,
+    }
+}
+
+/// Turns a failure or warning the annotations cover into a [`SuppressedFinding`];
+/// other findings are returned unchanged.
+fn suppress(finding: Finding, suppressions: &Suppressions) -> Result<Finding, SuppressedFinding> {
+    let (var_name, message) = match &finding {
+        Finding::MissingRequired(name) => (Some(name), "required variable is missing".to_string()),
+        Finding::MissingOptional(name) => (Some(name), "optional variable is missing".to_string()),
+        Finding::Invalid { var_name, message } => (Some(var_name), message.clone()),
+        Finding::Violation(message) => (None, message.clone()),
+        _ => return Ok(finding),
+    };
+
+    let annotation = if suppressions.skip {
+        SKIP_ANNOTATION
+    } else if var_name.is_some_and(|name| suppressions.ignored_vars.contains(name)) {
+        IGNORE_ANNOTATION
+    } else {
+        return Ok(finding);
+    };
+    Err(SuppressedFinding {
+        var_name: var_name.cloned(),
+        message,
+        annotation,
+    })
+}
+
+/// Fingerprint of a failure or warning; `None` for other findings.
+fn fingerprint(finding: &Finding, rule_id: &str, resource: &K8sResource, env_name: &str) -> Option<Fingerprint> {
+    let (var, message) = match finding {
+        Finding::MissingRequired(name) | Finding::MissingOptional(name) => (Some(name), None),
+        Finding::Invalid { var_name, message } => (Some(var_name), Some(message)),
+        Finding::Violation(message) => (None, Some(message)),
+        _ => return None,
+    };
+    let container = var.and_then(|var| resource.containers.iter().find(|c| c.env_vars.contains_key(var)));
+
+    Some(Fingerprint {
+        environment: env_name.to_string(),
+        kind: resource.kind.clone(),
+        workload: format!("{}/{}", resource.effective_namespace(), resource.name),
+        container: container.map(|c| c.name.clone()),
+        var: var.cloned(),
+        rule: rule_id.to_string(),
+        message: message.cloned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::k8s::K8sParser;
+    use std::path::Path;
+
+    fn config(yaml: &str) -> Config {
+        Config::lint(Path::new("envcheck.yaml"), yaml).unwrap()
+    }
+
+    fn resources(yaml: &str) -> Vec<K8sResource> {
+        K8sParser::new().parse_yaml(yaml).unwrap()
+    }
+
+    const PORT_CONFIG: &str = "\
+environments:
+  production:
+    required_vars:
+      - name: PORT
+        description: HTTP port
+        binds: [containerPort, readinessProbe]
+";
+
+    const PORT_DEPLOYMENT: &str = "\
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: web
+spec:
+  template:
+    spec:
+      containers:
+        - name: web
+          env:
+            - name: PORT
+              value: \"9090\"
+          ports:
+            - containerPort: 8080
+          readinessProbe:
+            httpGet:
+              port: 8081
+";
+
+    #[test]
+    fn findings_on_one_variable_have_separate_fingerprints() {
+        let checker = EnvChecker::new(config(PORT_CONFIG));
+        let results = checker.check_resources(&resources(PORT_DEPLOYMENT), "production");
+        assert_eq!(results[0].validation_errors.len(), 2);
+        assert_eq!(results[0].findings.len(), 2);
+        assert_ne!(results[0].findings[0], results[0].findings[1]);
+
+        let baseline = Baseline::from_results(&results);
+        assert_eq!(baseline.len(), 2);
+        let checker = EnvChecker::new(config(PORT_CONFIG)).with_baseline(baseline);
+        let results = checker.check_resources(&resources(PORT_DEPLOYMENT), "production");
+        assert_eq!(results[0].baselined.len(), 2);
+        assert!(matches!(results[0].status, CheckStatus::Passed));
+    }
+
+    #[test]
+    fn new_finding_on_baselined_variable_is_reported() {
+        let checker = EnvChecker::new(config(PORT_CONFIG));
+        let deployment = PORT_DEPLOYMENT.replace("port: 8081", "port: 9090");
+        let baseline = Baseline::from_results(&checker.check_resources(&resources(&deployment), "production"));
+        assert_eq!(baseline.len(), 1);
+
+        let checker = checker.with_baseline(baseline);
+        let results = checker.check_resources(&resources(PORT_DEPLOYMENT), "production");
+        assert_eq!(results[0].baselined.len(), 1);
+        assert!(matches!(results[0].status, CheckStatus::Failed));
+    }
+
+    #[test]
+    fn name_regex_matches_whole_name() {
+        let checker = EnvChecker::new(config(
+            "environments:\n  production:\n    required_vars: []\napplications:\n  web:\n    environments: [production]\n    match:\n      name_regex: web-[a-z]+\n",
+        ));
+        let workload = |name: &str| {
+            resources(&format!(
+                "apiVersion: apps/v1\nkind: Deployment\nmetadata:\n  name: {}\nspec:\n  template:\n    spec:\n      containers: []\n",
+                name
+            ))
+        };
+        assert_eq!(checker.application_for(&workload("web-api")[0]), Some("web"));
+        assert_eq!(checker.application_for(&workload("web-api-2")[0]), None);
+        assert_eq!(checker.application_for(&workload("old-web-api")[0]), None);
+    }
+
+    const COVERAGE_CONFIG: &str = "\
+environments:
+  production:
+    required_vars: []
+  staging:
+    required_vars: []
+applications:
+  web:
+    environments: [production, staging]
+  worker:
+    environments: [production]
+  batch:
+    environments: [staging]
+";
+
+    fn deployment(name: &str, namespace: &str) -> String {
+        format!(
+            "apiVersion: apps/v1\nkind: Deployment\nmetadata:\n  name: {}\n  namespace: {}\nspec:\n  template:\n    spec:\n      containers: []\n",
+            name, namespace
+        )
+    }
+
+    #[test]
+    fn coverage_reports_missing_applications_of_the_environment() {
+        let checker = EnvChecker::new(config(COVERAGE_CONFIG));
+        let findings = checker.check_coverage(&resources(&deployment("web", "shop")), "production");
+
+        let missing: Vec<(CoverageKind, &str, Severity)> =
+            findings.iter().map(|f| (f.kind, f.name.as_str(), f.severity)).collect();
+        assert_eq!(missing, [(CoverageKind::MissingApplication, "worker", Severity::Warning)]);
+        assert_eq!(
+            findings[0].message,
+            "Application 'worker' is configured for production but matches no rendered workload"
+        );
+    }
+
+    #[test]
+    fn coverage_reports_unconfigured_workloads_when_enabled() {
+        let yaml = format!("{}---\n{}", deployment("web", "shop"), deployment("cron", "ops"));
+        let checker = EnvChecker::new(config(COVERAGE_CONFIG));
+        let findings = checker.check_coverage(&resources(&yaml), "staging");
+        assert!(findings.iter().all(|f| f.kind != CoverageKind::UnconfiguredWorkload));
+
+        let config_yaml = format!(
+            "{}coverage:\n  missing_applications: off\n  unconfigured_workloads: error\n",
+            COVERAGE_CONFIG
+        );
+        let checker = EnvChecker::new(config(&config_yaml));
+        let findings = checker.check_coverage(&resources(&yaml), "staging");
+        let found: Vec<(CoverageKind, &str, Severity)> =
+            findings.iter().map(|f| (f.kind, f.name.as_str(), f.severity)).collect();
+        assert_eq!(found, [(CoverageKind::UnconfiguredWorkload, "ops/cron", Severity::Error)]);
+        assert_eq!(findings[0].message, "Deployment 'ops/cron' has no application entry");
+    }
+
+    const SUPPRESSION_CONFIG: &str = "\
+environments:
+  production:
+    required_vars:
+      - name: API_KEY
+        description: API key
+      - name: REDIS_URL
+        description: Cache
+  staging:
+    allow_suppressions: false
+    required_vars:
+      - name: API_KEY
+        description: API key
+";
+
+    fn annotated(annotation: &str) -> Vec<K8sResource> {
+        resources(&format!(
+            "apiVersion: apps/v1\nkind: DaemonSet\nmetadata:\n  name: debug\n  annotations:\n    {}\nspec:\n  template:\n    spec:\n      containers: []\n",
+            annotation
+        ))
+    }
+
+    #[test]
+    fn ignore_annotation_suppresses_listed_variables() {
+        let checker = EnvChecker::new(config(SUPPRESSION_CONFIG));
+        let result = &checker.check_resources(&annotated("envcheck.io/ignore: \"API_KEY\""), "production")[0];
+
+        assert_eq!(result.missing_required, ["REDIS_URL"]);
+        assert!(matches!(result.status, CheckStatus::Failed));
+        let suppressed: Vec<(Option<&str>, &str)> =
+            result.suppressed.iter().map(|f| (f.var_name.as_deref(), f.annotation)).collect();
+        assert_eq!(suppressed, [(Some("API_KEY"), IGNORE_ANNOTATION)]);
+    }
+
+    #[test]
+    fn skip_annotation_suppresses_every_finding() {
+        let checker = EnvChecker::new(config(SUPPRESSION_CONFIG));
+        let result = &checker.check_resources(&annotated("envcheck.io/skip: \"true\""), "production")[0];
+
+        assert!(result.missing_required.is_empty());
+        assert!(matches!(result.status, CheckStatus::Passed));
+        assert_eq!(result.suppressed.len(), 2);
+        assert!(result.suppressed.iter().all(|f| f.annotation == SKIP_ANNOTATION));
+        assert!(result.findings.is_empty());
+        assert_eq!(result.silenced.len(), 2);
+    }
 
-        if !regex.is_match(value) {
-            return Err(ValidationError {
-                var_name: var_name.to_string(),
-                message: format!("Value '{}' does not match pattern '{}'", value, pattern),
-            });
+    #[test]
+    fn suppressions_are_ignored_where_not_allowed() {
+        let checker = EnvChecker::new(config(SUPPRESSION_CONFIG));
+        for annotation in ["envcheck.io/ignore: \"API_KEY\"", "envcheck.io/skip: \"true\""] {
+            let result = &checker.check_resources(&annotated(annotation), "staging")[0];
+            assert_eq!(result.missing_required, ["API_KEY"], "{}", annotation);
+            assert!(result.suppressed.is_empty(), "{}", annotation);
         }
 
-        Ok(())
+        let checker = EnvChecker::new(config(SUPPRESSION_CONFIG)).with_suppressions_allowed(false);
+        let result = &checker.check_resources(&annotated("envcheck.io/skip: \"true\""), "production")[0];
+        assert_eq!(result.missing_required, ["API_KEY", "REDIS_URL"]);
+        assert!(result.suppressed.is_empty());
     }
-}
\ No newline at end of file
+}
@@ -1,16 +1,118 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use kustomize_envcheck::kustomize::{Renderer, DEFAULT_JOBS, DEFAULT_TIMEOUT_SECS};
+use kustomize_envcheck::output::OutputFormat;
 
 #[derive(Parser, Debug)]
 #[command(name = "kustomize-envcheck")]
 #[command(about = "Check environment variables in Kustomize-built Kubernetes manifests")]
 #[command(version)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Cli {
-    #[arg(short = 'k', long, help = "Path to Kustomize directory")]
-    pub kustomize_dir: String,
+    #[command(subcommand)]
+    pub command: Option<Command>,
 
-    #[arg(short = 'c', long, help = "Path to configuration file")]
+    #[command(flatten)]
+    pub check: CheckArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[command(subcommand, about = "Manage the kustomize build cache")]
+    Cache(CacheCommand),
+
+    #[command(subcommand, about = "Work with configuration files")]
+    Config(ConfigCommand),
+
+    #[command(about = "Generate a starter configuration from existing overlays")]
+    Init(InitArgs),
+
+    #[command(about = "List the checks and their IDs")]
+    Rules {
+        #[arg(short = 'c', long, help = "Also list the plugins of this configuration")]
+        config: Option<String>,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct InitArgs {
+    #[arg(
+        short = 'k',
+        long,
+        required = true,
+        help = "Overlay to inspect; its directory name becomes the environment name (repeat for each environment)"
+    )]
+    pub kustomize_dir: Vec<String>,
+
+    #[arg(
+        short = 'c',
+        long,
+        default_value = "envcheck.yaml",
+        help = "Where to write the configuration; an existing file is never overwritten"
+    )]
     pub config: String,
 
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = DEFAULT_TIMEOUT_SECS,
+        help = "Timeout for each kustomize build"
+    )]
+    pub timeout: u64,
+
+    #[arg(
+        short = 'j',
+        long,
+        default_value_t = DEFAULT_JOBS,
+        help = "Maximum number of kustomize builds to run concurrently"
+    )]
+    pub jobs: usize,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "kustomize",
+        help = "How overlays are rendered"
+    )]
+    pub renderer: RendererArg,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    #[command(about = "Validate a configuration file and report every problem with its location")]
+    Lint {
+        #[arg(short = 'c', long, help = "Path to configuration file or directory of fragments")]
+        config: String,
+    },
+
+    #[command(about = "Print the JSON Schema for configuration files")]
+    Schema,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    #[command(about = "Remove cached build output")]
+    Prune {
+        #[arg(long, value_name = "DIR", help = "Cache directory [default: $XDG_CACHE_HOME/kustomize-envcheck]")]
+        cache_dir: Option<String>,
+
+        #[arg(long, value_name = "DAYS", help = "Only remove entries older than this many days")]
+        older_than: Option<u64>,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct CheckArgs {
+    #[arg(
+        short = 'k',
+        long,
+        required = true,
+        help = "Path to Kustomize directory (repeat to check several overlays)"
+    )]
+    pub kustomize_dir: Vec<String>,
+
+    #[arg(short = 'c', long, required = true, help = "Path to configuration file or directory of fragments")]
+    pub config: Option<String>,
+
     #[arg(short = 'e', long, help = "Specific environment to check")]
     pub environment: Option<String>,
 
@@ -21,17 +123,105 @@ pub struct Cli {
         default_value = "text",
         help = "Output format"
     )]
-    pub output: OutputFormat,
+    pub output: OutputFormatArg,
 
     #[arg(short = 'v', long, help = "Verbose output")]
     pub verbose: bool,
 
+    #[arg(
+        long,
+        value_enum,
+        default_value = "error",
+        help = "Lowest severity of finding that makes the run fail"
+    )]
+    pub fail_on: FailOn,
+
     #[arg(long, help = "Show extra environment variables not defined in config")]
     pub show_extra_vars: bool,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = DEFAULT_TIMEOUT_SECS,
+        help = "Timeout for each kustomize build"
+    )]
+    pub timeout: u64,
+
+    #[arg(
+        short = 'j',
+        long,
+        default_value_t = DEFAULT_JOBS,
+        help = "Maximum number of kustomize builds to run concurrently"
+    )]
+    pub jobs: usize,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "kustomize",
+        help = "How overlays are rendered"
+    )]
+    pub renderer: RendererArg,
+
+    #[arg(long, value_name = "ID", help = "Skip a check by ID (repeatable); see the rules subcommand")]
+    pub disable_rule: Vec<String>,
+
+    #[arg(long, help = "Ignore envcheck.io/ignore and envcheck.io/skip annotations on workloads")]
+    pub no_suppressions: bool,
+
+    #[arg(long, value_name = "FILE", help = "Only fail on findings not recorded in this baseline file")]
+    pub baseline: Option<String>,
+
+    #[arg(long, value_name = "FILE", help = "Record the current findings in a baseline file")]
+    pub write_baseline: Option<String>,
+
+    #[arg(long, help = "Always run kustomize instead of reusing cached build output")]
+    pub no_cache: bool,
+
+    #[arg(long, value_name = "DIR", help = "Cache directory [default: $XDG_CACHE_HOME/kustomize-envcheck]")]
+    pub cache_dir: Option<String>,
 }
 
-#[derive(Debug, Clone, clap::ValueEnum)]
-pub enum OutputFormat {
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailOn {
+    /// Fail on warnings and errors.
+    Warning,
+    /// Fail on errors only.
+    Error,
+    /// Never fail because of findings.
+    Never,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RendererArg {
+    /// Run the kustomize binary
+    Kustomize,
+    /// Use the built-in renderer for a subset of kustomize
+    #[cfg(feature = "builtin-kustomize")]
+    Builtin,
+}
+
+impl From<RendererArg> for Renderer {
+    fn from(renderer: RendererArg) -> Self {
+        match renderer {
+            RendererArg::Kustomize => Renderer::Kustomize,
+            #[cfg(feature = "builtin-kustomize")]
+            RendererArg::Builtin => Renderer::Builtin,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormatArg {
     Text,
     Json,
-}
\ No newline at end of file
+}
+
+impl From<OutputFormatArg> for OutputFormat {
+    fn from(format: OutputFormatArg) -> Self {
+        match format {
+            OutputFormatArg::Text => OutputFormat::Text,
+            OutputFormatArg::Json => OutputFormat::Json,
+        }
+    }
+}
@@ -1,53 +1,750 @@
-use anyhow::Result;
+use crate::diagnostics::{position_at, ConfigErrors, Diagnostic, Position, SourceMap};
+use crate::expr::Expression;
+use crate::provenance::normalize;
+use crate::rules::{BINDINGS, BUILTIN_RULE_IDS};
+use crate::error::{Error, Result};
+use regex::Regex;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Contents of `envcheck.yaml` and the files it includes.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+#[serde(deny_unknown_fields)]
+#[non_exhaustive]
 pub struct Config {
+    /// Other configuration files to merge in, as paths or globs relative to this file.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Variables every workload needs, keyed by environment name.
+    #[serde(default)]
     pub environments: HashMap<String, Environment>,
+    /// Per-application settings, keyed by application name.
+    #[serde(default)]
     pub applications: HashMap<String, Application>,
+    #[serde(default)]
+    pub app_identity: AppIdentity,
+    #[serde(default)]
+    pub coverage: Coverage,
+    #[serde(default)]
+    pub environment_selection: EnvironmentSelection,
+    /// WebAssembly modules run as additional checks, keyed by check ID.
+    #[serde(default)]
+    pub plugins: HashMap<String, Plugin>,
+    /// Severity of each check's findings, keyed by check ID.
+    #[serde(default)]
+    pub severity: HashMap<String, Severity>,
+    /// Dot-separated path of the pod spec in custom workload kinds, keyed by
+    /// `apiVersion/kind` (for example `example.com/v1/Worker: spec.podSpec`).
+    #[serde(default)]
+    pub workload_kinds: HashMap<String, String>,
+}
+
+/// A check implemented by a WebAssembly module; see the README for its interface.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+#[non_exhaustive]
+pub struct Plugin {
+    /// Path to the `.wasm` module, relative to the file that declares it.
+    pub path: PathBuf,
+    /// Shown by `kustomize-envcheck rules`.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Instructions the module may execute per workload before it is stopped.
+    #[serde(default = "default_plugin_fuel")]
+    pub fuel: u64,
+    /// Passed to the module unchanged.
+    #[serde(default)]
+    pub config: serde_json::Value,
+}
+
+fn default_plugin_fuel() -> u64 {
+    10_000_000
+}
+
+/// What to check against when `--environment` is not given.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EnvironmentSelection {
+    #[serde(default)]
+    pub when_unset: WhenUnset,
+    /// Environment used when `when_unset` is `default`.
+    #[serde(default = "default_environment")]
+    pub default: String,
+}
+
+impl Default for EnvironmentSelection {
+    fn default() -> Self {
+        Self {
+            when_unset: WhenUnset::default(),
+            default: default_environment(),
+        }
+    }
+}
+
+fn default_environment() -> String {
+    "development".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WhenUnset {
+    /// Refuse to run without `--environment`.
+    Error,
+    /// Use the nearest overlay path component that names a configured environment.
+    Infer,
+    /// Use `environment_selection.default`.
+    #[default]
+    Default,
+}
+
+/// How much a finding matters; `off` drops it.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Off,
+    Info,
+    Warning,
+    Error,
+}
+
+/// Severity of findings about which workloads the configuration covers.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Coverage {
+    /// A configured application for the selected environment matches no rendered workload.
+    #[serde(default = "default_missing_applications")]
+    pub missing_applications: Severity,
+    /// A rendered workload matches no entry under `applications`.
+    #[serde(default = "default_unconfigured_workloads")]
+    pub unconfigured_workloads: Severity,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self {
+            missing_applications: default_missing_applications(),
+            unconfigured_workloads: default_unconfigured_workloads(),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+fn default_missing_applications() -> Severity {
+    Severity::Warning
+}
+
+fn default_unconfigured_workloads() -> Severity {
+    Severity::Off
+}
+
+/// How rendered workloads are matched to entries under `applications` when an
+/// application has no `match` selector of its own.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AppIdentity {
+    #[serde(default)]
+    pub by: IdentitySource,
+    /// Label or annotation key holding the application name.
+    #[serde(default = "default_identity_key")]
+    pub key: String,
+}
+
+impl Default for AppIdentity {
+    fn default() -> Self {
+        Self {
+            by: IdentitySource::default(),
+            key: default_identity_key(),
+        }
+    }
+}
+
+fn default_identity_key() -> String {
+    "app.kubernetes.io/name".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdentitySource {
+    /// `metadata.name` equals the application name.
+    #[default]
+    Name,
+    /// `metadata.name` with the overlay's namePrefix/nameSuffix removed equals the application name.
+    StrippedName,
+    /// The `key` label equals the application name.
+    Label,
+    /// The `key` annotation equals the application name.
+    Annotation,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+#[non_exhaustive]
 pub struct Environment {
+    /// Variables that must be set; a missing one fails the check.
     pub required_vars: Vec<EnvVar>,
+    /// Variables that only produce a warning when missing.
     #[serde(default)]
     pub optional_vars: Vec<EnvVar>,
+    /// Conditional requirements between variables.
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// Whether workloads may suppress findings with the `envcheck.io/ignore` and
+    /// `envcheck.io/skip` annotations; when false the annotations are ignored.
+    #[serde(default = "default_allow_suppressions")]
+    pub allow_suppressions: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+fn default_allow_suppressions() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+#[non_exhaustive]
 pub struct Application {
+    /// Environments the application is deployed to.
     pub environments: Vec<String>,
+    /// Required variables on top of the environment's own.
     #[serde(default)]
     pub additional_vars: Vec<EnvVar>,
+    /// Selects the application's workloads instead of `app_identity`.
+    #[serde(default, rename = "match")]
+    pub selector: Option<AppSelector>,
+    /// Conditional requirements checked in the application's environments.
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+/// A requirement between variables, checked only while every `when` condition holds.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+#[serde(deny_unknown_fields)]
+#[non_exhaustive]
+pub struct Rule {
+    /// Variables and the value, or list of values, each must have for the rule to apply.
+    /// An unset variable is compared using its configured `default`.
+    #[serde(default)]
+    pub when: BTreeMap<String, Expected>,
+    /// Variables that must be set.
+    #[serde(default)]
+    pub requires: Vec<String>,
+    /// Exactly one of these variables must be set.
+    #[serde(default)]
+    pub one_of: Vec<String>,
+    /// At most one of these variables may be set.
+    #[serde(default)]
+    pub mutually_exclusive: Vec<String>,
+    /// Expression that must evaluate to true, e.g. `int(WORKER_THREADS) <= int(MAX_CONNECTIONS)`.
+    #[serde(default)]
+    pub expr: Option<String>,
+    /// Replaces the generated failure message.
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// Value or values a `when` condition accepts.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(untagged)]
+pub enum Expected {
+    One(ScalarValue),
+    AnyOf(Vec<ScalarValue>),
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+impl Expected {
+    pub fn values(&self) -> &[ScalarValue] {
+        match self {
+            Expected::One(value) => std::slice::from_ref(value),
+            Expected::AnyOf(values) => values,
+        }
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        self.values().iter().any(|expected| expected.0 == value)
+    }
+}
+
+/// A string, boolean or integer, compared as a string so that `TLS_ENABLED: true`
+/// equals the environment value "true". Booleans become `true`/`false` and
+/// integers their decimal form whatever their spelling; decimal numbers must be
+/// quoted, as `1.0` would otherwise not survive parsing as written.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct ScalarValue(pub String);
+
+impl<'de> Deserialize<'de> for ScalarValue {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ScalarVisitor;
+
+        impl serde::de::Visitor<'_> for ScalarVisitor {
+            type Value = ScalarValue;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a string, boolean or integer (quote decimal numbers, e.g. \"1.0\")")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<ScalarValue, E> {
+                Ok(ScalarValue(value.to_string()))
+            }
+
+            fn visit_bool<E: serde::de::Error>(self, value: bool) -> Result<ScalarValue, E> {
+                Ok(ScalarValue(value.to_string()))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, value: i64) -> Result<ScalarValue, E> {
+                Ok(ScalarValue(value.to_string()))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<ScalarValue, E> {
+                Ok(ScalarValue(value.to_string()))
+            }
+
+            fn visit_f64<E: serde::de::Error>(self, value: f64) -> Result<ScalarValue, E> {
+                Err(E::invalid_type(serde::de::Unexpected::Float(value), &self))
+            }
+        }
+
+        deserializer.deserialize_any(ScalarVisitor)
+    }
+}
+
+impl JsonSchema for ScalarValue {
+    fn schema_name() -> String {
+        "ScalarValue".to_string()
+    }
+
+    fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        use schemars::schema::{InstanceType, SchemaObject, SingleOrVec};
+        SchemaObject {
+            instance_type: Some(SingleOrVec::Vec(vec![InstanceType::String, InstanceType::Boolean, InstanceType::Integer])),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// Per-application workload selector. Every field that is set must match.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct AppSelector {
+    /// Label key whose value must equal `value` (or the application name).
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Annotation key whose value must equal `value` (or the application name).
+    #[serde(default)]
+    pub annotation: Option<String>,
+    /// Expected label or annotation value.
+    #[serde(default)]
+    pub value: Option<String>,
+    /// Regular expression that must match the whole `metadata.name`.
+    #[serde(default)]
+    pub name_regex: Option<String>,
+    /// Glob (`*`, `?`) that must match the whole `metadata.name`.
+    #[serde(default)]
+    pub name_glob: Option<String>,
+    /// Glob that must match the workload's namespace (`default` when unset).
+    #[serde(default)]
+    pub namespace: Option<String>,
+}
+
+impl AppSelector {
+    pub fn is_empty(&self) -> bool {
+        self.label.is_none()
+            && self.annotation.is_none()
+            && self.name_regex.is_none()
+            && self.name_glob.is_none()
+            && self.namespace.is_none()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+#[non_exhaustive]
 pub struct EnvVar {
     pub name: String,
     pub description: String,
+    /// Value the application falls back to when the variable is unset.
     #[serde(default)]
     pub default: Option<String>,
+    /// Regular expression the value must match.
     #[serde(default)]
     pub pattern: Option<String>,
+    /// Namespace globs the variable applies to; empty means every namespace.
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+    /// Severity of findings about the variable, overriding the check's.
+    #[serde(default)]
+    pub severity: Option<Severity>,
+    /// Container fields the value must agree with, e.g. `containerPort`.
+    #[serde(default)]
+    pub binds: Option<Binds>,
+}
+
+/// Field or fields of a container a variable is bound to.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(untagged)]
+pub enum Binds {
+    One(BoundField),
+    AllOf(Vec<BoundField>),
+}
+
+impl Binds {
+    pub fn fields(&self) -> &[BoundField] {
+        match self {
+            Binds::One(field) => std::slice::from_ref(field),
+            Binds::AllOf(fields) => fields,
+        }
+    }
+}
+
+/// A port in the container spec a variable can be bound to.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum BoundField {
+    /// One of the container's `ports[].containerPort`.
+    ContainerPort,
+    /// The port of the `readinessProbe`.
+    ReadinessProbe,
+    /// The port of the `livenessProbe`.
+    LivenessProbe,
+    /// The port of the `startupProbe`.
+    StartupProbe,
+}
+
+impl EnvVar {
+    pub fn applies_to_namespace(&self, namespace: &str) -> bool {
+        self.namespaces.is_empty() || self.namespaces.iter().any(|glob| glob_to_regex(glob).is_match(namespace))
+    }
 }
 
 impl Config {
-    pub fn from_file(path: &Path) -> Result<Self> {
-        let content = std::fs::read_to_string(path)?;
-        let config: Config = serde_yaml::from_str(&content)?;
-        Ok(config)
+    /// Loads a YAML, JSON or TOML configuration file, or every such file in a
+    /// directory in name order, following `include` entries.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        Self::lint_path(path).map_err(|diagnostics| Error::Config(ConfigErrors(diagnostics)))
+    }
+
+    /// Loads and validates a configuration file or directory, returning every
+    /// problem found instead of stopping at the first one.
+    pub fn lint_path(path: &Path) -> Result<Self, Vec<Diagnostic>> {
+        let mut diagnostics = Vec::new();
+        let roots = if path.is_dir() {
+            fragment_files(path).map_err(|message| vec![Diagnostic { file: path.to_path_buf(), position: None, message }])?
+        } else {
+            vec![path.to_path_buf()]
+        };
+
+        let mut fragments = Vec::new();
+        let mut visited = HashSet::new();
+        for root in &roots {
+            load_fragments(root, &mut visited, &mut fragments, &mut diagnostics);
+        }
+        if !diagnostics.is_empty() {
+            diagnostics.sort_by(|a, b| (&a.file, a.position).cmp(&(&b.file, b.position)));
+            return Err(diagnostics);
+        }
+        Self::assemble(path, fragments)
+    }
+
+    /// Validates a single configuration held in memory. `include` entries are not followed.
+    pub fn lint(path: &Path, content: &str) -> Result<Self, Vec<Diagnostic>> {
+        let fragment = Fragment::parse(path, content)?;
+        Self::assemble(path, vec![fragment])
     }
 
-    pub fn get_required_vars(&self, app_name: &str, env_name: &str) -> Vec<&EnvVar> {
+    /// Merges fragments in load order and validates the result. Environments,
+    /// applications and top-level settings may each be defined by one fragment only.
+    fn assemble(root: &Path, fragments: Vec<Fragment>) -> Result<Self, Vec<Diagnostic>> {
+        let mut config = Config::default();
+        let mut diagnostics = Vec::new();
+        // Which fragment defined each environment, application or setting, keyed by config path.
+        let mut owners: HashMap<String, usize> = HashMap::new();
+
+        for (index, fragment) in fragments.iter().enumerate() {
+            let mut claim = |node: String, what: String| -> bool {
+                match owners.get(&node) {
+                    Some(&owner) => {
+                        diagnostics.push(Diagnostic {
+                            file: fragment.file.clone(),
+                            position: fragment.source.locate(&node),
+                            message: format!("{} is already defined in {}", what, fragments[owner].describe(&node)),
+                        });
+                        false
+                    }
+                    None => {
+                        owners.insert(node, index);
+                        true
+                    }
+                }
+            };
+
+            for key in ["app_identity", "coverage", "environment_selection"] {
+                if fragment.source.position(key).is_some() && claim(key.to_string(), format!("Setting '{}'", key)) {
+                    match key {
+                        "app_identity" => config.app_identity = fragment.config.app_identity.clone(),
+                        "coverage" => config.coverage = fragment.config.coverage.clone(),
+                        _ => config.environment_selection = fragment.config.environment_selection.clone(),
+                    }
+                }
+            }
+            let mut env_names: Vec<&String> = fragment.config.environments.keys().collect();
+            env_names.sort();
+            for name in env_names {
+                if claim(format!("environments.{}", name), format!("Environment '{}'", name)) {
+                    config.environments.insert(name.clone(), fragment.config.environments[name].clone());
+                }
+            }
+            let mut app_names: Vec<&String> = fragment.config.applications.keys().collect();
+            app_names.sort();
+            for name in app_names {
+                if claim(format!("applications.{}", name), format!("Application '{}'", name)) {
+                    config.applications.insert(name.clone(), fragment.config.applications[name].clone());
+                }
+            }
+            let mut plugin_ids: Vec<&String> = fragment.config.plugins.keys().collect();
+            plugin_ids.sort();
+            for id in plugin_ids {
+                if claim(format!("plugins.{}", id), format!("Plugin '{}'", id)) {
+                    let mut plugin = fragment.config.plugins[id].clone();
+                    let dir = fragment.file.parent().unwrap_or(Path::new(""));
+                    plugin.path = normalize(&dir.join(&plugin.path));
+                    config.plugins.insert(id.clone(), plugin);
+                }
+            }
+            let mut check_ids: Vec<&String> = fragment.config.severity.keys().collect();
+            check_ids.sort();
+            for id in check_ids {
+                if claim(format!("severity.{}", id), format!("Severity of check '{}'", id)) {
+                    config.severity.insert(id.clone(), fragment.config.severity[id]);
+                }
+            }
+            let mut kinds: Vec<&String> = fragment.config.workload_kinds.keys().collect();
+            kinds.sort();
+            for kind in kinds {
+                if claim(format!("workload_kinds.{}", kind), format!("Workload kind '{}'", kind)) {
+                    config.workload_kinds.insert(kind.clone(), fragment.config.workload_kinds[kind].clone());
+                }
+            }
+        }
+
+        if config.environments.is_empty() {
+            diagnostics.push(Diagnostic {
+                file: root.to_path_buf(),
+                position: None,
+                message: "No environments are defined".to_string(),
+            });
+        }
+
+        for (node, message) in config.validate() {
+            // Paths name an environment or application, so the longest owning prefix wins.
+            let fragment = owners
+                .iter()
+                .filter(|(owned, _)| {
+                    node.strip_prefix(owned.as_str())
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+                })
+                .max_by_key(|(owned, _)| owned.len())
+                .map(|(_, &index)| &fragments[index]);
+            diagnostics.push(match fragment {
+                Some(fragment) => Diagnostic {
+                    file: fragment.file.clone(),
+                    position: fragment.source.locate(&node),
+                    message: fragment.source.resolve_references(&message),
+                },
+                None => Diagnostic { file: root.to_path_buf(), position: None, message },
+            });
+        }
+
+        if diagnostics.is_empty() {
+            return Ok(config);
+        }
+        diagnostics.sort_by(|a, b| (&a.file, a.position).cmp(&(&b.file, b.position)));
+        Err(diagnostics)
+    }
+
+    /// Semantic checks serde cannot express, as (config path, message) pairs.
+    /// Messages may mention other nodes as `{path}`, replaced by their line.
+    fn validate(&self) -> Vec<(String, String)> {
+        let mut problems = Vec::new();
+
+        let mut env_names: Vec<&String> = self.environments.keys().collect();
+        env_names.sort();
+        for env_name in env_names {
+            let env = &self.environments[env_name];
+            for (i, rule) in env.rules.iter().enumerate() {
+                validate_rule(rule, &format!("environments.{}.rules[{}]", env_name, i), &mut problems);
+            }
+            let mut seen: HashMap<&str, String> = HashMap::new();
+            for (list, vars) in [("required_vars", &env.required_vars), ("optional_vars", &env.optional_vars)] {
+                for (i, var) in vars.iter().enumerate() {
+                    let node = format!("environments.{}.{}[{}]", env_name, list, i);
+                    validate_var(var, &node, &mut problems);
+                    if let Some(first) = seen.get(var.name.as_str()) {
+                        problems.push((
+                            format!("{}.name", node),
+                            format!("Duplicate variable '{}' (first defined at {{{}}})", var.name, first),
+                        ));
+                    } else {
+                        seen.insert(&var.name, node);
+                    }
+                }
+            }
+        }
+
+        let mut app_names: Vec<&String> = self.applications.keys().collect();
+        app_names.sort();
+        for app_name in app_names {
+            let app = &self.applications[app_name];
+            for (i, rule) in app.rules.iter().enumerate() {
+                validate_rule(rule, &format!("applications.{}.rules[{}]", app_name, i), &mut problems);
+            }
+            for (i, env_name) in app.environments.iter().enumerate() {
+                if !self.environments.contains_key(env_name) {
+                    problems.push((
+                        format!("applications.{}.environments[{}]", app_name, i),
+                        format!(
+                            "Application '{}' references undefined environment '{}'.{}",
+                            app_name,
+                            env_name,
+                            suggestion(env_name, &self.environment_names())
+                        ),
+                    ));
+                }
+            }
+
+            let mut seen: HashMap<&str, String> = HashMap::new();
+            for (i, var) in app.additional_vars.iter().enumerate() {
+                let node = format!("applications.{}.additional_vars[{}]", app_name, i);
+                validate_var(var, &node, &mut problems);
+                let shadowed = app.environments.iter().find(|env_name| {
+                    self.environments
+                        .get(*env_name)
+                        .is_some_and(|env| env.required_vars.iter().chain(&env.optional_vars).any(|v| v.name == var.name))
+                });
+                if let Some(first) = seen.get(var.name.as_str()) {
+                    problems.push((
+                        format!("{}.name", node),
+                        format!("Duplicate variable '{}' (first defined at {{{}}})", var.name, first),
+                    ));
+                } else if let Some(env_name) = shadowed {
+                    problems.push((
+                        format!("{}.name", node),
+                        format!("Duplicate variable '{}' (also defined by environment '{}')", var.name, env_name),
+                    ));
+                }
+                seen.entry(&var.name).or_insert(node);
+            }
+
+            if let Some(pattern) = app.selector.as_ref().and_then(|s| s.name_regex.as_ref()) {
+                if let Err(e) = Regex::new(pattern) {
+                    problems.push((
+                        format!("applications.{}.match.name_regex", app_name),
+                        format!("Invalid regex: {}", summary_line(&e.to_string())),
+                    ));
+                }
+            }
+        }
+
+        let mut plugin_ids: Vec<&String> = self.plugins.keys().collect();
+        plugin_ids.sort();
+        for id in plugin_ids {
+            if BUILTIN_RULE_IDS.contains(&id.as_str()) {
+                problems.push((format!("plugins.{}", id), format!("Plugin ID '{}' is used by a built-in check", id)));
+            }
+            let path = &self.plugins[id].path;
+            if !path.is_file() {
+                problems.push((format!("plugins.{}.path", id), format!("Plugin module {} does not exist", path.display())));
+            }
+        }
+
+        let mut check_ids: Vec<&str> = BUILTIN_RULE_IDS.to_vec();
+        check_ids.extend(self.plugins.keys().map(String::as_str));
+        let mut severity_ids: Vec<&String> = self.severity.keys().collect();
+        severity_ids.sort();
+        for id in severity_ids {
+            if !check_ids.contains(&id.as_str()) {
+                problems.push((
+                    format!("severity.{}", id),
+                    format!("Unknown check '{}'.{}", id, suggestion(id, &check_ids)),
+                ));
+            }
+        }
+
+        let mut kinds: Vec<&String> = self.workload_kinds.keys().collect();
+        kinds.sort();
+        for kind in kinds {
+            let node = format!("workload_kinds.{}", kind);
+            if !kind.rsplit_once('/').is_some_and(|(api_version, kind)| !api_version.is_empty() && !kind.is_empty()) {
+                problems.push((node, format!("Workload kind '{}' is not of the form apiVersion/kind", kind)));
+            } else if crate::k8s::split_path(&self.workload_kinds[kind]).is_empty() {
+                problems.push((node, format!("Pod spec path of workload kind '{}' is empty", kind)));
+            }
+        }
+
+        problems
+    }
+
+    /// Determines the environment to check an overlay against, failing on names
+    /// that are not defined under `environments`.
+    pub fn resolve_environment(&self, requested: Option<&str>, overlay: &Path) -> Result<String> {
+        if let Some(name) = requested {
+            return self.known_environment(name, "--environment");
+        }
+
+        let selection = &self.environment_selection;
+        match selection.when_unset {
+            WhenUnset::Error => Err(Error::Environment(format!(
+                "No --environment given; choose one of: {}",
+                self.environment_names().join(", ")
+            ))),
+            WhenUnset::Default => self.known_environment(&selection.default, "environment_selection.default"),
+            WhenUnset::Infer => overlay
+                .components()
+                .rev()
+                .filter_map(|c| match c {
+                    Component::Normal(name) => name.to_str(),
+                    _ => None,
+                })
+                .find(|name| self.environments.contains_key(*name))
+                .map(str::to_string)
+                .ok_or_else(|| {
+                    Error::Environment(format!(
+                        "Could not infer an environment from {}; no path component is one of: {}. Pass --environment",
+                        overlay.display(),
+                        self.environment_names().join(", ")
+                    ))
+                }),
+        }
+    }
+
+    fn known_environment(&self, name: &str, source: &str) -> Result<String> {
+        if self.environments.contains_key(name) {
+            return Ok(name.to_string());
+        }
+
+        let names = self.environment_names();
+        Err(Error::Environment(format!(
+            "Unknown environment '{}' from {}.{} Defined environments: {}",
+            name,
+            source,
+            suggestion(name, &names),
+            names.join(", ")
+        )))
+    }
+
+    fn environment_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.environments.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    pub fn get_required_vars(&self, app_name: Option<&str>, env_name: &str) -> Vec<&EnvVar> {
         let mut vars = Vec::new();
 
         if let Some(env) = self.environments.get(env_name) {
             vars.extend(&env.required_vars);
         }
 
-        if let Some(app) = self.applications.get(app_name) {
+        if let Some(app) = app_name.and_then(|name| self.applications.get(name)) {
             if app.environments.contains(&env_name.to_string()) {
                 vars.extend(&app.additional_vars);
             }
@@ -56,7 +753,12 @@ impl Config {
         vars
     }
 
-    pub fn get_optional_vars(&self, _app_name: &str, env_name: &str) -> Vec<&EnvVar> {
+    /// Whether workloads in the environment may suppress findings with annotations.
+    pub fn allows_suppressions(&self, env_name: &str) -> bool {
+        self.environments.get(env_name).is_none_or(|env| env.allow_suppressions)
+    }
+
+    pub fn get_optional_vars(&self, _app_name: Option<&str>, env_name: &str) -> Vec<&EnvVar> {
         let mut vars = Vec::new();
 
         if let Some(env) = self.environments.get(env_name) {
@@ -65,4 +767,357 @@ impl Config {
 
         vars
     }
-}
\ No newline at end of file
+
+    pub fn get_rules(&self, app_name: Option<&str>, env_name: &str) -> Vec<&Rule> {
+        let mut rules = Vec::new();
+
+        if let Some(env) = self.environments.get(env_name) {
+            rules.extend(&env.rules);
+        }
+
+        if let Some(app) = app_name.and_then(|name| self.applications.get(name)) {
+            if app.environments.contains(&env_name.to_string()) {
+                rules.extend(&app.rules);
+            }
+        }
+
+        rules
+    }
+}
+
+/// Configuration file syntax, chosen by extension. Anything unrecognised is read as YAML.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl Format {
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Format::Json,
+            Some("toml") => Format::Toml,
+            _ => Format::Yaml,
+        }
+    }
+}
+
+/// One configuration file before fragments are merged.
+struct Fragment {
+    file: PathBuf,
+    config: Config,
+    source: SourceMap,
+}
+
+impl Fragment {
+    fn parse(file: &Path, content: &str) -> Result<Self, Vec<Diagnostic>> {
+        let diagnostic = |position: Option<Position>, message: String| {
+            vec![Diagnostic { file: file.to_path_buf(), position, message }]
+        };
+        // serde_yaml and serde_json append the location to the message; it is shown separately.
+        let without_location = |message: String, position: Option<Position>| match position {
+            Some(p) => message
+                .strip_suffix(&format!(" at line {} column {}", p.line, p.column))
+                .map(str::to_string)
+                .unwrap_or(message),
+            None => message,
+        };
+
+        let format = Format::of(file);
+        let parsed = match format {
+            Format::Yaml => serde_yaml::from_str(content).map_err(|e| {
+                let position = e.location().map(|l| Position { line: l.line(), column: l.column() });
+                diagnostic(position, without_location(e.to_string(), position))
+            }),
+            Format::Json => serde_json::from_str(content).map_err(|e| {
+                let position = Some(Position { line: e.line(), column: e.column() });
+                diagnostic(position, without_location(e.to_string(), position))
+            }),
+            Format::Toml => toml::from_str(content).map_err(|e| {
+                let position = e.span().map(|span| position_at(content, span.start));
+                diagnostic(position, e.message().trim().replace('\n', "; "))
+            }),
+        };
+        let config: Config = parsed?;
+
+        let source = match format {
+            Format::Toml => SourceMap::from_toml(content),
+            Format::Yaml | Format::Json => SourceMap::from_yaml(content),
+        };
+        let duplicates: Vec<Diagnostic> = source
+            .duplicate_keys()
+            .iter()
+            .map(|(node, position)| Diagnostic {
+                file: file.to_path_buf(),
+                position: Some(*position),
+                message: format!("Duplicate key '{}'", node),
+            })
+            .collect();
+        if !duplicates.is_empty() {
+            return Err(duplicates);
+        }
+
+        Ok(Self {
+            file: file.to_path_buf(),
+            config,
+            source,
+        })
+    }
+
+    /// `file:line:column` of `node`, for naming the other side of a conflict.
+    fn describe(&self, node: &str) -> String {
+        match self.source.locate(node) {
+            Some(position) => format!("{}:{}:{}", self.file.display(), position.line, position.column),
+            None => self.file.display().to_string(),
+        }
+    }
+}
+
+/// Loads `file` followed by everything it includes, depth first. Each file is
+/// loaded once, so repeated or circular includes are harmless.
+fn load_fragments(file: &Path, visited: &mut HashSet<PathBuf>, fragments: &mut Vec<Fragment>, diagnostics: &mut Vec<Diagnostic>) {
+    if !visited.insert(file.canonicalize().unwrap_or_else(|_| file.to_path_buf())) {
+        return;
+    }
+    let content = match std::fs::read_to_string(file) {
+        Ok(content) => content,
+        Err(e) => {
+            diagnostics.push(Diagnostic { file: file.to_path_buf(), position: None, message: format!("Failed to read: {}", e) });
+            return;
+        }
+    };
+    let fragment = match Fragment::parse(file, &content) {
+        Ok(fragment) => fragment,
+        Err(problems) => {
+            diagnostics.extend(problems);
+            return;
+        }
+    };
+
+    let dir = file.parent().unwrap_or(Path::new(""));
+    let mut included = Vec::new();
+    for (i, pattern) in fragment.config.include.iter().enumerate() {
+        let full = if dir.as_os_str().is_empty() || Path::new(pattern).is_absolute() {
+            pattern.clone()
+        } else {
+            format!("{}/{}", glob::Pattern::escape(&dir.display().to_string()), pattern)
+        };
+        let problem = |message: String| Diagnostic {
+            file: file.to_path_buf(),
+            position: fragment.source.locate(&format!("include[{}]", i)),
+            message,
+        };
+        match glob::glob(&full) {
+            Ok(paths) => {
+                let matches: Vec<PathBuf> =
+                    paths.filter_map(Result::ok).filter(|p| p.is_file()).map(|p| normalize(&p)).collect();
+                if matches.is_empty() {
+                    diagnostics.push(problem(format!("Include '{}' matched no files", pattern)));
+                }
+                included.extend(matches);
+            }
+            Err(e) => diagnostics.push(problem(format!("Invalid include pattern '{}': {}", pattern, e.msg))),
+        }
+    }
+
+    fragments.push(fragment);
+    for path in included {
+        load_fragments(&path, visited, fragments, diagnostics);
+    }
+}
+
+/// Configuration files directly inside `dir`, sorted by name.
+fn fragment_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("Failed to read directory: {}", e))?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.is_file() && matches!(path.extension().and_then(|e| e.to_str()), Some("yaml" | "yml" | "json" | "toml"))
+        })
+        .collect();
+    if files.is_empty() {
+        return Err("Directory contains no .yaml, .yml, .json or .toml files".to_string());
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Checks that a variable does not use a name reserved for rule expressions, its
+/// own pattern, and that its default satisfies it.
+fn validate_var(var: &EnvVar, node: &str, problems: &mut Vec<(String, String)>) {
+    if BINDINGS.contains(&var.name.as_str()) {
+        problems.push((
+            format!("{}.name", node),
+            format!("Variable name '{}' is reserved for rule expressions", var.name),
+        ));
+    }
+    let Some(pattern) = &var.pattern else {
+        return;
+    };
+    match Regex::new(pattern) {
+        Ok(regex) => {
+            if let Some(default) = var.default.as_ref().filter(|d| !regex.is_match(d)) {
+                problems.push((
+                    format!("{}.default", node),
+                    format!("Default '{}' for '{}' does not match pattern '{}'", default, var.name, pattern),
+                ));
+            }
+        }
+        Err(e) => problems.push((
+            format!("{}.pattern", node),
+            format!("Invalid regex for '{}': {}", var.name, summary_line(&e.to_string())),
+        )),
+    }
+}
+
+/// Checks that a rule asks for something and that each list can be satisfied.
+fn validate_rule(rule: &Rule, node: &str, problems: &mut Vec<(String, String)>) {
+    if rule.requires.is_empty() && rule.one_of.is_empty() && rule.mutually_exclusive.is_empty() && rule.expr.is_none() {
+        problems.push((node.to_string(), "Rule needs requires, one_of, mutually_exclusive or expr".to_string()));
+    }
+    if let Some(Err(e)) = rule.expr.as_deref().map(Expression::parse) {
+        problems.push((format!("{}.expr", node), format!("Invalid expression: {}", e)));
+    }
+    if !rule.requires.is_empty() && rule.when.is_empty() {
+        problems.push((
+            format!("{}.requires", node),
+            "Rule uses requires without when; list unconditional variables under required_vars".to_string(),
+        ));
+    }
+    for (key, vars) in [("one_of", &rule.one_of), ("mutually_exclusive", &rule.mutually_exclusive)] {
+        if vars.len() == 1 {
+            problems.push((format!("{}.{}", node, key), format!("{} needs at least two variables", key)));
+        }
+    }
+    for (var, expected) in &rule.when {
+        if expected.values().is_empty() {
+            problems.push((format!("{}.when.{}", node, var), format!("Condition on '{}' lists no values", var)));
+        }
+    }
+}
+
+/// regex errors render the pattern with a caret underneath; keep the summary line.
+fn summary_line(message: &str) -> &str {
+    let line = message.lines().last().map(str::trim).unwrap_or(message);
+    line.strip_prefix("error: ").unwrap_or(line)
+}
+
+/// " Did you mean 'x'?" for the closest candidate, or an empty string.
+fn suggestion(name: &str, candidates: &[&str]) -> String {
+    candidates
+        .iter()
+        .map(|candidate| (strsim::damerau_levenshtein(name, candidate), *candidate))
+        .filter(|(distance, candidate)| *distance <= (candidate.len() / 3).max(2))
+        .min()
+        .map(|(_, candidate)| format!(" Did you mean '{}'?", candidate))
+        .unwrap_or_default()
+}
+
+/// Translates a glob using `*` and `?` wildcards into an anchored regular expression.
+pub fn glob_to_regex(glob: &str) -> Regex {
+    let mut pattern = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            other => pattern.push_str(&regex::escape(&other.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern).expect("escaped glob forms a valid pattern")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Diagnostics of `content` as `file:line:column: message` lines.
+    fn lint(content: &str) -> Vec<String> {
+        match Config::lint(Path::new("envcheck.yaml"), content) {
+            Ok(_) => Vec::new(),
+            Err(diagnostics) => diagnostics.iter().map(Diagnostic::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn unknown_keys_are_located() {
+        assert_eq!(
+            lint("environments:\n  production:\n    requried_vars: []\n"),
+            ["envcheck.yaml:3:5: environments.production: unknown field `requried_vars`, expected one of `required_vars`, `optional_vars`, `rules`, `allow_suppressions`"]
+        );
+    }
+
+    #[test]
+    fn semantic_problems_are_all_reported_with_lines() {
+        let content = "\
+environments:
+  production:
+    required_vars:
+      - name: PORT
+        description: HTTP port
+        pattern: \"[0-9\"
+      - name: PORT
+        description: Again
+applications:
+  web:
+    environments: [prodution]
+";
+        assert_eq!(
+            lint(content),
+            [
+                "envcheck.yaml:6:18: Invalid regex for 'PORT': unclosed character class",
+                "envcheck.yaml:7:15: Duplicate variable 'PORT' (first defined at line 4)",
+                "envcheck.yaml:11:20: Application 'web' references undefined environment 'prodution'. Did you mean 'production'?",
+            ]
+        );
+    }
+
+    /// Writes `files` into a temporary directory, removed when it is dropped.
+    fn fixture(files: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    const WEB: &str = "applications:\n  web:\n    environments: [production]\n";
+
+    #[test]
+    fn fragments_redefining_an_application_name_both_files() {
+        let dir = fixture(&[
+            ("10-base.yaml", &format!("environments:\n  production:\n    required_vars: []\n{}", WEB)),
+            ("20-team.yaml", WEB),
+        ]);
+        let diagnostics = Config::lint_path(dir.path()).unwrap_err();
+        let messages: Vec<String> = diagnostics.iter().map(Diagnostic::to_string).collect();
+        let file = |name: &str| dir.path().join(name).display().to_string();
+        assert_eq!(
+            messages,
+            [format!("{}:2:3: Application 'web' is already defined in {}:5:3", file("20-team.yaml"), file("10-base.yaml"))]
+        );
+    }
+
+    #[test]
+    fn included_fragments_merge_and_conflict() {
+        let dir = fixture(&[
+            ("envcheck.yaml", "include: [teams/*.yaml]\nenvironments:\n  production:\n    required_vars: []\n"),
+            ("teams/a.yaml", WEB),
+            ("teams/b.yaml", "applications:\n  worker:\n    environments: [production]\n"),
+        ]);
+        let config = Config::from_path(&dir.path().join("envcheck.yaml")).unwrap();
+        let mut names: Vec<&String> = config.applications.keys().collect();
+        names.sort();
+        assert_eq!(names, ["web", "worker"]);
+
+        std::fs::write(dir.path().join("teams/b.yaml"), WEB).unwrap();
+        let diagnostics = Config::lint_path(&dir.path().join("envcheck.yaml")).unwrap_err();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].file.ends_with("teams/b.yaml"), "{}", diagnostics[0]);
+        assert!(diagnostics[0].message.contains("teams/a.yaml:2:3"), "{}", diagnostics[0]);
+    }
+}
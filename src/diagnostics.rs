@@ -0,0 +1,253 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+
+/// 1-based line and column in a configuration file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A problem found in a configuration file, printed as `file:line:column: message`.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub file: PathBuf,
+    pub position: Option<Position>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some(position) => write!(f, "{}:{}:{}: {}", self.file.display(), position.line, position.column, self.message),
+            None => write!(f, "{}: {}", self.file.display(), self.message),
+        }
+    }
+}
+
+/// Every diagnostic that made a configuration file unusable.
+#[derive(Debug)]
+pub struct ConfigErrors(pub Vec<Diagnostic>);
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} problem(s) found", self.0.len())?;
+        for diagnostic in &self.0 {
+            write!(f, "\n  {}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigErrors {}
+
+/// Maps config paths such as `environments.production.required_vars[0].pattern`
+/// to where that node starts in the source file.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    nodes: HashMap<String, Position>,
+    duplicates: Vec<(String, Position)>,
+}
+
+impl SourceMap {
+    /// Builds the map from YAML source; content that fails to parse yields an
+    /// empty map, since serde reports the syntax error itself.
+    pub fn from_yaml(content: &str) -> Self {
+        let mut builder = SourceMapBuilder::default();
+        if Parser::new_from_str(content).load(&mut builder, false).is_err() {
+            return Self::default();
+        }
+        Self {
+            nodes: builder.nodes,
+            duplicates: builder.duplicates,
+        }
+    }
+
+    /// Builds the map from TOML source; content that fails to parse yields an
+    /// empty map, since serde reports the syntax error itself.
+    pub fn from_toml(content: &str) -> Self {
+        let Ok(document) = toml_edit::ImDocument::parse(content) else {
+            return Self::default();
+        };
+        let mut map = Self::default();
+        map.walk_toml(content, "", document.as_item(), None);
+        map
+    }
+
+    fn walk_toml(&mut self, content: &str, path: &str, item: &toml_edit::Item, key: Option<usize>) {
+        let own = item.span().map(|span| span.start);
+        // As in YAML, containers point at their key and scalars at their value.
+        let start = if item.is_table_like() || item.is_array() || item.is_array_of_tables() {
+            key.or(own)
+        } else {
+            own.or(key)
+        };
+        if let Some(offset) = start.filter(|_| !path.is_empty()) {
+            self.nodes.entry(path.to_string()).or_insert(position_at(content, offset));
+        }
+
+        let child = |name: &str| if path.is_empty() { name.to_string() } else { format!("{}.{}", path, name) };
+        if let Some(table) = item.as_table_like() {
+            for (name, _) in table.iter() {
+                if let Some((key, value)) = table.get_key_value(name) {
+                    self.walk_toml(content, &child(name), value, key.span().map(|span| span.start));
+                }
+            }
+        } else if let Some(array) = item.as_array() {
+            for (i, value) in array.iter().enumerate() {
+                let element = toml_edit::Item::Value(value.clone());
+                self.walk_toml(content, &format!("{}[{}]", path, i), &element, None);
+            }
+        } else if let Some(tables) = item.as_array_of_tables() {
+            for (i, table) in tables.iter().enumerate() {
+                let element = toml_edit::Item::Table(table.clone());
+                self.walk_toml(content, &format!("{}[{}]", path, i), &element, None);
+            }
+        }
+    }
+
+    /// Mapping keys that appear more than once, with the position of each repeat.
+    /// YAML and TOML parsers reject these themselves; JSON parsers keep the last one.
+    pub fn duplicate_keys(&self) -> &[(String, Position)] {
+        &self.duplicates
+    }
+
+    /// Position of `path` itself, if it appears in the source.
+    pub fn position(&self, path: &str) -> Option<Position> {
+        self.nodes.get(path).copied()
+    }
+
+    /// Position of `path`, or of its nearest ancestor present in the source
+    /// (a defaulted field points at the entry that omits it).
+    pub fn locate(&self, path: &str) -> Option<Position> {
+        let mut path = path;
+        loop {
+            if let Some(position) = self.nodes.get(path) {
+                return Some(*position);
+            }
+            path = &path[..path.rfind(['.', '['])?];
+        }
+    }
+
+    /// Replaces each `{path}` in `message` with `line N`, or the bare path if it
+    /// cannot be located.
+    pub fn resolve_references(&self, message: &str) -> String {
+        let mut resolved = String::new();
+        let mut rest = message;
+        while let Some(start) = rest.find('{') {
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            let path = &rest[start + 1..start + len];
+            resolved.push_str(&rest[..start]);
+            match self.locate(path) {
+                Some(position) => resolved.push_str(&format!("line {}", position.line)),
+                None => resolved.push_str(path),
+            }
+            rest = &rest[start + len + 1..];
+        }
+        resolved.push_str(rest);
+        resolved
+    }
+}
+
+/// Position of the byte `offset` in `content`.
+pub fn position_at(content: &str, offset: usize) -> Position {
+    let before = &content[..offset.min(content.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+    }
+}
+
+enum Frame {
+    Mapping {
+        path: String,
+        key: Option<(String, Marker)>,
+        seen: HashSet<String>,
+        /// Set until the first key is read, for mappings positioned at that key.
+        unplaced: bool,
+    },
+    Sequence { path: String, index: usize },
+}
+
+#[derive(Default)]
+struct SourceMapBuilder {
+    stack: Vec<Frame>,
+    nodes: HashMap<String, Position>,
+    duplicates: Vec<(String, Position)>,
+}
+
+impl SourceMapBuilder {
+    /// Records a node that starts at `mark`, returning its path unless it is a mapping key.
+    fn enter(&mut self, event: &Event, mark: Marker) -> Option<String> {
+        let is_container = matches!(event, Event::MappingStart(..) | Event::SequenceStart(..));
+        let (path, mark) = match self.stack.last_mut() {
+            None => (String::new(), mark),
+            Some(Frame::Mapping { path, key: key @ None, seen, unplaced }) => {
+                let name = match event {
+                    Event::Scalar(value, ..) => value.clone(),
+                    _ => String::from("?"),
+                };
+                if !seen.insert(name.clone()) {
+                    let path = if path.is_empty() { name.clone() } else { format!("{}.{}", path, name) };
+                    self.duplicates.push((path, Position { line: mark.line(), column: mark.col() + 1 }));
+                }
+                if std::mem::take(unplaced) {
+                    self.nodes.insert(path.clone(), Position { line: mark.line(), column: mark.col() + 1 });
+                }
+                *key = Some((name, mark));
+                return None;
+            }
+            Some(Frame::Mapping { path, key, .. }) => {
+                let (name, key_mark) = key.take().expect("key was recorded");
+                // Block mappings and sequences start on the line after their key,
+                // so point at the key instead.
+                let mark = if is_container { key_mark } else { mark };
+                (if path.is_empty() { name } else { format!("{}.{}", path, name) }, mark)
+            }
+            Some(Frame::Sequence { path, index }) => {
+                *index += 1;
+                let path = format!("{}[{}]", path, *index - 1);
+                // A block mapping inside a sequence is reported past its first key,
+                // so it is placed when that key is read.
+                if matches!(event, Event::MappingStart(..)) {
+                    return Some(path);
+                }
+                (path, mark)
+            }
+        };
+        self.nodes.entry(path.clone()).or_insert(Position {
+            line: mark.line(),
+            column: mark.col() + 1,
+        });
+        Some(path)
+    }
+}
+
+impl MarkedEventReceiver for SourceMapBuilder {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(..) | Event::Alias(..) => {
+                self.enter(&event, mark);
+            }
+            Event::MappingStart(..) => {
+                let unplaced = matches!(self.stack.last(), Some(Frame::Sequence { .. }));
+                let path = self.enter(&event, mark).unwrap_or_default();
+                self.stack.push(Frame::Mapping { path, key: None, seen: HashSet::new(), unplaced });
+            }
+            Event::SequenceStart(..) => {
+                let path = self.enter(&event, mark).unwrap_or_default();
+                self.stack.push(Frame::Sequence { path, index: 0 });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use crate::diagnostics::ConfigErrors;
+use crate::kustomize::KustomizeError;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Errors returned by the library.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The configuration could not be read or failed validation.
+    Config(ConfigErrors),
+    /// No environment could be chosen for an overlay, or the chosen one is not defined.
+    Environment(String),
+    /// `kustomize version` could not be run or reported a failure.
+    KustomizeNotInstalled { source: Option<std::io::Error> },
+    /// An overlay could not be rendered.
+    Kustomize(KustomizeError),
+    /// Rendered manifests are not valid YAML.
+    Manifest(serde_yaml::Error),
+    /// A file or directory could not be read or written.
+    Io { path: PathBuf, source: std::io::Error },
+    /// Results could not be serialized.
+    Output(serde_json::Error),
+    /// A WebAssembly plugin could not be loaded.
+    Plugin { path: PathBuf, message: String },
+    /// A baseline file is not valid.
+    Baseline { path: PathBuf, message: String },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config(errors) => write!(f, "{}", errors),
+            Error::Environment(message) => write!(f, "{}", message),
+            Error::KustomizeNotInstalled { .. } => write!(f, "Kustomize is not installed or not in PATH"),
+            Error::Kustomize(e) => write!(f, "{}", e),
+            Error::Manifest(_) => write!(f, "Invalid Kubernetes YAML"),
+            Error::Io { path, .. } => write!(f, "Failed to access {}", path.display()),
+            Error::Output(_) => write!(f, "Failed to serialize results"),
+            Error::Plugin { path, message } => write!(f, "Failed to load plugin {}: {}", path.display(), message),
+            Error::Baseline { path, message } => write!(f, "Invalid baseline {}: {}", path.display(), message),
+        }
+    }
+}
+
+// Messages leave out the wrapped error, which `source` returns. `Kustomize` is
+// displayed as the wrapped error itself, so it passes on that error's source.
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::KustomizeNotInstalled { source } => source.as_ref().map(|e| e as _),
+            Error::Kustomize(e) => e.source(),
+            Error::Manifest(e) => Some(e),
+            Error::Io { source, .. } => Some(source),
+            Error::Output(e) => Some(e),
+            Error::Config(_) | Error::Environment(_) | Error::Plugin { .. } | Error::Baseline { .. } => None,
+        }
+    }
+}
+
+impl From<ConfigErrors> for Error {
+    fn from(e: ConfigErrors) -> Self {
+        Error::Config(e)
+    }
+}
+
+impl From<KustomizeError> for Error {
+    fn from(e: KustomizeError) -> Self {
+        Error::Kustomize(e)
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(e: serde_yaml::Error) -> Self {
+        Error::Manifest(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Output(e)
+    }
+}
@@ -0,0 +1,100 @@
+//! Kubernetes `$(VAR)` references in env values, command and args. `$$` escapes
+//! a `$`, and references that cannot be resolved are left as written.
+
+/// Expands the references in `input` that `lookup` resolves.
+pub fn expand<'a>(input: &str, lookup: impl Fn(&str) -> Option<&'a str>) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        match read_reference(rest) {
+            Token::Escape => {
+                output.push('$');
+                rest = &rest[1..];
+            }
+            Token::Reference(name) => {
+                match lookup(name) {
+                    Some(value) => output.push_str(value),
+                    None => {
+                        output.push_str("$(");
+                        output.push_str(name);
+                        output.push(')');
+                    }
+                }
+                rest = &rest[name.len() + 2..];
+            }
+            Token::Literal => output.push('$'),
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Names referenced by `input`, in order, skipping escaped `$$(...)`.
+pub fn references(input: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = input;
+    while let Some(start) = rest.find('$') {
+        rest = &rest[start + 1..];
+        match read_reference(rest) {
+            Token::Escape => rest = &rest[1..],
+            Token::Reference(name) => {
+                names.push(name);
+                rest = &rest[name.len() + 2..];
+            }
+            Token::Literal => {}
+        }
+    }
+    names
+}
+
+enum Token<'a> {
+    /// `$$`
+    Escape,
+    /// `$(name)`
+    Reference(&'a str),
+    /// A `$` that starts neither, including an unterminated `$(` and an empty `$()`.
+    Literal,
+}
+
+/// Reads what follows a `$`.
+fn read_reference(after: &str) -> Token<'_> {
+    if after.starts_with('$') {
+        return Token::Escape;
+    }
+    match after.strip_prefix('(').and_then(|inner| Some(&inner[..inner.find(')')?])) {
+        Some(name) if !name.is_empty() => Token::Reference(name),
+        _ => Token::Literal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<&'static str> {
+        match name {
+            "HOST" => Some("db"),
+            "PORT" => Some("5432"),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn expands_known_references() {
+        assert_eq!(expand("$(HOST):$(PORT)", lookup), "db:5432");
+        assert_eq!(expand("$(MISSING)/$(HOST)", lookup), "$(MISSING)/db");
+    }
+
+    #[test]
+    fn escapes_and_literals() {
+        assert_eq!(expand("$$(HOST) $ $(HOST", lookup), "$(HOST) $ $(HOST");
+        assert_eq!(expand("cost: $()", lookup), "cost: $()");
+    }
+
+    #[test]
+    fn lists_references() {
+        assert_eq!(references("$(A) $$(B) $() $(C) $(D"), vec!["A", "C"]);
+    }
+}
@@ -0,0 +1,783 @@
+//! A small, side-effect free expression language modelled on CEL, used by
+//! `expr` rules. Expressions have no loops or assignments, so evaluation is
+//! bounded by their size. The depth of the parsed tree, whether from nesting or
+//! from chains of operators, and regex size are capped as well.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+const MAX_DEPTH: usize = 128;
+const MAX_REGEX_SIZE: usize = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Double(f64),
+    String(String),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "bool",
+            Value::Int(_) => "int",
+            Value::Double(_) => "double",
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Double(d) => write!(f, "{}", d),
+            Value::String(s) => write!(f, "{:?}", s),
+            Value::List(items) => {
+                let items: Vec<String> = items.iter().map(Value::to_string).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Value::Map(entries) => {
+                let entries: Vec<String> = entries.iter().map(|(k, v)| format!("{:?}: {}", k, v)).collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Literal(Value),
+    Ident(String),
+    List(Vec<Node>),
+    Not(Box<Node>),
+    Neg(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Conditional(Box<Node>, Box<Node>, Box<Node>),
+    Member(Box<Node>, String),
+    Index(Box<Node>, Box<Node>),
+    Call(String, Vec<Node>),
+}
+
+/// A parsed expression, ready to be evaluated against any number of bindings.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0, depth: 0 };
+        let root = parser.conditional()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(Self { root }),
+            Some((token, column)) => Err(format!("unexpected {} at column {}", token, column)),
+        }
+    }
+
+    /// Identifiers the expression reads, in order of first use.
+    pub fn identifiers(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        collect_identifiers(&self.root, &mut names);
+        names
+    }
+
+    /// Evaluates against `bindings`; unknown identifiers evaluate to `null`.
+    pub fn evaluate(&self, bindings: &HashMap<String, Value>) -> Result<Value, String> {
+        eval(&self.root, bindings)
+    }
+}
+
+fn collect_identifiers<'a>(node: &'a Node, names: &mut Vec<&'a str>) {
+    match node {
+        Node::Literal(_) => {}
+        Node::Ident(name) => {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+        Node::List(items) | Node::Call(_, items) => items.iter().for_each(|n| collect_identifiers(n, names)),
+        Node::Not(inner) | Node::Neg(inner) | Node::Member(inner, _) => collect_identifiers(inner, names),
+        Node::And(a, b) | Node::Or(a, b) | Node::Binary(_, a, b) | Node::Index(a, b) => {
+            collect_identifiers(a, names);
+            collect_identifiers(b, names);
+        }
+        Node::Conditional(a, b, c) => {
+            collect_identifiers(a, names);
+            collect_identifiers(b, names);
+            collect_identifiers(c, names);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(i64),
+    Double(f64),
+    String(String),
+    Ident(String),
+    Punct(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Int(i) => write!(f, "'{}'", i),
+            Token::Double(d) => write!(f, "'{}'", d),
+            Token::String(s) => write!(f, "{:?}", s),
+            Token::Ident(name) => write!(f, "'{}'", name),
+            Token::Punct(p) => write!(f, "'{}'", p),
+        }
+    }
+}
+
+const PUNCTUATION: [&str; 22] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "+", "-", "*", "/", "%", "(", ")", "[", "]", ",", ".", "?", ":",
+];
+
+const FUNCTIONS: [&str; 11] = [
+    "int", "double", "string", "bool", "size", "has", "url", "matches", "startsWith", "endsWith", "contains",
+];
+
+/// Splits `source` into tokens, each paired with its 1-based column.
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let token = if text.contains('.') {
+                text.parse().map(Token::Double).map_err(|_| format!("invalid number '{}' at column {}", text, column))?
+            } else {
+                text.parse().map(Token::Int).map_err(|_| format!("integer '{}' out of range at column {}", text, column))?
+            };
+            tokens.push((token, column));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((Token::Ident(chars[start..i].iter().collect()), column));
+        } else if c == '"' || c == '\'' {
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(format!("unterminated string starting at column {}", column)),
+                    Some(&q) if q == c => break,
+                    Some('\\') => {
+                        i += 1;
+                        value.push(match chars.get(i) {
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some(&other @ ('\\' | '"' | '\'')) => other,
+                            _ => return Err(format!("invalid escape at column {}", i)),
+                        });
+                    }
+                    Some(&other) => value.push(other),
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push((Token::String(value), column));
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let punct = PUNCTUATION
+                .iter()
+                .find(|p| rest.starts_with(*p))
+                .ok_or_else(|| format!("unexpected character '{}' at column {}", c, column))?;
+            i += punct.chars().count();
+            tokens.push((Token::Punct(punct), column));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Punct(p)) if *p == punct);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn function(&self, name: String, column: usize) -> Result<String, String> {
+        if FUNCTIONS.contains(&name.as_str()) {
+            Ok(name)
+        } else {
+            Err(format!("unknown function '{}' at column {}", name, column))
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), String> {
+        if self.eat(punct) {
+            return Ok(());
+        }
+        Err(match self.tokens.get(self.pos) {
+            Some((token, column)) => format!("expected '{}' but found {} at column {}", punct, token, column),
+            None => format!("expected '{}' but the expression ended", punct),
+        })
+    }
+
+    /// Enters a level of the tree; each call is undone by `ascend`. Operator
+    /// chains descend once per operator, since `a || b || c` nests like `(a || b) || c`.
+    fn descend(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(format!("expression is nested more than {} levels deep", MAX_DEPTH));
+        }
+        Ok(())
+    }
+
+    fn ascend(&mut self, levels: usize) {
+        self.depth -= levels;
+    }
+
+    fn conditional(&mut self) -> Result<Node, String> {
+        self.descend()?;
+        let condition = self.or()?;
+        let node = if self.eat("?") {
+            let then = self.conditional()?;
+            self.expect(":")?;
+            let otherwise = self.conditional()?;
+            Node::Conditional(Box::new(condition), Box::new(then), Box::new(otherwise))
+        } else {
+            condition
+        };
+        self.ascend(1);
+        Ok(node)
+    }
+
+    fn or(&mut self) -> Result<Node, String> {
+        let mut node = self.and()?;
+        let mut levels = 0;
+        while self.eat("||") {
+            self.descend()?;
+            levels += 1;
+            node = Node::Or(Box::new(node), Box::new(self.and()?));
+        }
+        self.ascend(levels);
+        Ok(node)
+    }
+
+    fn and(&mut self) -> Result<Node, String> {
+        let mut node = self.relation()?;
+        let mut levels = 0;
+        while self.eat("&&") {
+            self.descend()?;
+            levels += 1;
+            node = Node::And(Box::new(node), Box::new(self.relation()?));
+        }
+        self.ascend(levels);
+        Ok(node)
+    }
+
+    fn relation(&mut self) -> Result<Node, String> {
+        let mut node = self.additive()?;
+        let mut levels = 0;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Punct("==")) => BinaryOp::Eq,
+                Some(Token::Punct("!=")) => BinaryOp::Ne,
+                Some(Token::Punct("<")) => BinaryOp::Lt,
+                Some(Token::Punct("<=")) => BinaryOp::Le,
+                Some(Token::Punct(">")) => BinaryOp::Gt,
+                Some(Token::Punct(">=")) => BinaryOp::Ge,
+                Some(Token::Ident(word)) if word == "in" => BinaryOp::In,
+                _ => break,
+            };
+            self.pos += 1;
+            self.descend()?;
+            levels += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.additive()?));
+        }
+        self.ascend(levels);
+        Ok(node)
+    }
+
+    fn additive(&mut self) -> Result<Node, String> {
+        let mut node = self.multiplicative()?;
+        let mut levels = 0;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Punct("+")) => BinaryOp::Add,
+                Some(Token::Punct("-")) => BinaryOp::Sub,
+                _ => break,
+            };
+            self.pos += 1;
+            self.descend()?;
+            levels += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.multiplicative()?));
+        }
+        self.ascend(levels);
+        Ok(node)
+    }
+
+    fn multiplicative(&mut self) -> Result<Node, String> {
+        let mut node = self.unary()?;
+        let mut levels = 0;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Punct("*")) => BinaryOp::Mul,
+                Some(Token::Punct("/")) => BinaryOp::Div,
+                Some(Token::Punct("%")) => BinaryOp::Rem,
+                _ => break,
+            };
+            self.pos += 1;
+            self.descend()?;
+            levels += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.unary()?));
+        }
+        self.ascend(levels);
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        self.descend()?;
+        let node = if self.eat("!") {
+            Node::Not(Box::new(self.unary()?))
+        } else if self.eat("-") {
+            Node::Neg(Box::new(self.unary()?))
+        } else {
+            self.postfix()?
+        };
+        self.ascend(1);
+        Ok(node)
+    }
+
+    fn postfix(&mut self) -> Result<Node, String> {
+        let mut node = self.primary()?;
+        let mut levels = 0;
+        loop {
+            if matches!(self.peek(), Some(Token::Punct("." | "["))) {
+                self.descend()?;
+                levels += 1;
+            }
+            if self.eat(".") {
+                let (name, column) = match self.tokens.get(self.pos) {
+                    Some((Token::Ident(name), column)) => (name.clone(), *column),
+                    Some((token, column)) => return Err(format!("expected a field name but found {} at column {}", token, column)),
+                    None => return Err("expected a field name but the expression ended".to_string()),
+                };
+                self.pos += 1;
+                node = if self.eat("(") {
+                    // Method calls pass the receiver as the first argument.
+                    let mut args = vec![node];
+                    args.extend(self.arguments(")")?);
+                    Node::Call(self.function(name, column)?, args)
+                } else {
+                    Node::Member(Box::new(node), name)
+                };
+            } else if self.eat("[") {
+                let index = self.conditional()?;
+                self.expect("]")?;
+                node = Node::Index(Box::new(node), Box::new(index));
+            } else {
+                self.ascend(levels);
+                return Ok(node);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Node, String> {
+        let Some((token, column)) = self.tokens.get(self.pos).cloned() else {
+            return Err("unexpected end of expression".to_string());
+        };
+        self.pos += 1;
+        match token {
+            Token::Int(i) => Ok(Node::Literal(Value::Int(i))),
+            Token::Double(d) => Ok(Node::Literal(Value::Double(d))),
+            Token::String(s) => Ok(Node::Literal(Value::String(s))),
+            Token::Ident(name) => match name.as_str() {
+                "true" => Ok(Node::Literal(Value::Bool(true))),
+                "false" => Ok(Node::Literal(Value::Bool(false))),
+                "null" => Ok(Node::Literal(Value::Null)),
+                _ if self.eat("(") => Ok(Node::Call(self.function(name, column)?, self.arguments(")")?)),
+                _ => Ok(Node::Ident(name)),
+            },
+            Token::Punct("(") => {
+                let node = self.conditional()?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            Token::Punct("[") => Ok(Node::List(self.arguments("]")?)),
+            other => Err(format!("unexpected {} at column {}", other, column)),
+        }
+    }
+
+    fn arguments(&mut self, close: &str) -> Result<Vec<Node>, String> {
+        let mut args = Vec::new();
+        if self.eat(close) {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.conditional()?);
+            if self.eat(close) {
+                return Ok(args);
+            }
+            self.expect(",")?;
+        }
+    }
+}
+
+fn eval(node: &Node, bindings: &HashMap<String, Value>) -> Result<Value, String> {
+    match node {
+        Node::Literal(value) => Ok(value.clone()),
+        Node::Ident(name) => Ok(bindings.get(name).cloned().unwrap_or(Value::Null)),
+        Node::List(items) => Ok(Value::List(items.iter().map(|n| eval(n, bindings)).collect::<Result<_, _>>()?)),
+        Node::Not(inner) => match eval(inner, bindings)? {
+            Value::Bool(b) => Ok(Value::Bool(!b)),
+            other => Err(format!("'!' needs a bool, got {}", other.type_name())),
+        },
+        Node::Neg(inner) => match eval(inner, bindings)? {
+            Value::Int(i) => i.checked_neg().map(Value::Int).ok_or_else(|| "integer overflow".to_string()),
+            Value::Double(d) => Ok(Value::Double(-d)),
+            other => Err(format!("'-' needs a number, got {}", other.type_name())),
+        },
+        Node::And(a, b) => Ok(Value::Bool(truth(a, bindings, "&&")? && truth(b, bindings, "&&")?)),
+        Node::Or(a, b) => Ok(Value::Bool(truth(a, bindings, "||")? || truth(b, bindings, "||")?)),
+        Node::Conditional(condition, then, otherwise) => {
+            if truth(condition, bindings, "?:")? {
+                eval(then, bindings)
+            } else {
+                eval(otherwise, bindings)
+            }
+        }
+        Node::Binary(op, a, b) => binary(*op, eval(a, bindings)?, eval(b, bindings)?),
+        Node::Member(target, field) => match eval(target, bindings)? {
+            Value::Map(mut entries) => Ok(entries.remove(field).unwrap_or(Value::Null)),
+            Value::Null => Err(format!("cannot read '{}' of null", field)),
+            other => Err(format!("cannot read '{}' of a {}", field, other.type_name())),
+        },
+        Node::Index(target, index) => match (eval(target, bindings)?, eval(index, bindings)?) {
+            (Value::List(items), Value::Int(i)) => usize::try_from(i)
+                .ok()
+                .and_then(|i| items.into_iter().nth(i))
+                .ok_or_else(|| format!("index {} out of range", i)),
+            (Value::Map(mut entries), Value::String(key)) => Ok(entries.remove(&key).unwrap_or(Value::Null)),
+            (target, index) => Err(format!("cannot index a {} with a {}", target.type_name(), index.type_name())),
+        },
+        Node::Call(name, args) => {
+            let args: Vec<Value> = args.iter().map(|n| eval(n, bindings)).collect::<Result<_, _>>()?;
+            call(name, args)
+        }
+    }
+}
+
+fn truth(node: &Node, bindings: &HashMap<String, Value>, op: &str) -> Result<bool, String> {
+    match eval(node, bindings)? {
+        Value::Bool(b) => Ok(b),
+        other => Err(format!("'{}' needs bools, got {}", op, other.type_name())),
+    }
+}
+
+fn binary(op: BinaryOp, a: Value, b: Value) -> Result<Value, String> {
+    use BinaryOp::*;
+    let mismatch = |symbol: &str, a: &Value, b: &Value| {
+        Err(format!("cannot apply '{}' to {} and {}", symbol, a.type_name(), b.type_name()))
+    };
+    match op {
+        Eq => Ok(Value::Bool(equal(&a, &b))),
+        Ne => Ok(Value::Bool(!equal(&a, &b))),
+        Lt | Le | Gt | Ge => {
+            let ordering = match (&a, &b) {
+                (Value::String(x), Value::String(y)) => x.partial_cmp(y),
+                _ => match (number(&a), number(&b)) {
+                    (Some(x), Some(y)) => x.partial_cmp(&y),
+                    _ => return mismatch("<", &a, &b),
+                },
+            };
+            let Some(ordering) = ordering else {
+                return Ok(Value::Bool(false));
+            };
+            Ok(Value::Bool(match op {
+                Lt => ordering.is_lt(),
+                Le => ordering.is_le(),
+                Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            }))
+        }
+        In => match b {
+            Value::List(items) => Ok(Value::Bool(items.iter().any(|item| equal(&a, item)))),
+            Value::Map(entries) => match a {
+                Value::String(key) => Ok(Value::Bool(entries.contains_key(&key))),
+                _ => Ok(Value::Bool(false)),
+            },
+            _ => mismatch("in", &a, &b),
+        },
+        Add | Sub | Mul | Div | Rem => match (a, b) {
+            (Value::Int(x), Value::Int(y)) => {
+                let result = match op {
+                    Add => x.checked_add(y),
+                    Sub => x.checked_sub(y),
+                    Mul => x.checked_mul(y),
+                    Div if y == 0 => return Err("division by zero".to_string()),
+                    Div => x.checked_div(y),
+                    Rem if y == 0 => return Err("division by zero".to_string()),
+                    _ => x.checked_rem(y),
+                };
+                result.map(Value::Int).ok_or_else(|| "integer overflow".to_string())
+            }
+            (Value::String(x), Value::String(y)) if op == Add => Ok(Value::String(x + &y)),
+            (Value::List(mut x), Value::List(y)) if op == Add => {
+                x.extend(y);
+                Ok(Value::List(x))
+            }
+            (a, b) => match (number(&a), number(&b)) {
+                (Some(x), Some(y)) => Ok(Value::Double(match op {
+                    Add => x + y,
+                    Sub => x - y,
+                    Mul => x * y,
+                    Div => x / y,
+                    _ => x % y,
+                })),
+                _ => mismatch(
+                    match op {
+                        Add => "+",
+                        Sub => "-",
+                        Mul => "*",
+                        Div => "/",
+                        _ => "%",
+                    },
+                    &a,
+                    &b,
+                ),
+            },
+        },
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Int(i) => Some(*i as f64),
+        Value::Double(d) => Some(*d),
+        _ => None,
+    }
+}
+
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Int(_), Value::Double(_)) | (Value::Double(_), Value::Int(_)) => number(a) == number(b),
+        _ => a == b,
+    }
+}
+
+fn call(name: &str, args: Vec<Value>) -> Result<Value, String> {
+    let arity = |n: usize| {
+        if args.len() == n {
+            Ok(())
+        } else {
+            Err(format!("{}() takes {} argument(s), got {}", name, n, args.len()))
+        }
+    };
+    match name {
+        "int" => {
+            arity(1)?;
+            match &args[0] {
+                Value::Int(i) => Ok(Value::Int(*i)),
+                Value::Double(d) if d.is_finite() => Ok(Value::Int(d.trunc() as i64)),
+                Value::String(s) => s.trim().parse().map(Value::Int).map_err(|_| format!("int() cannot parse {:?}", s)),
+                other => Err(format!("int() cannot convert {}", other.type_name())),
+            }
+        }
+        "double" => {
+            arity(1)?;
+            match &args[0] {
+                Value::String(s) => s.trim().parse().map(Value::Double).map_err(|_| format!("double() cannot parse {:?}", s)),
+                other => number(other).map(Value::Double).ok_or_else(|| format!("double() cannot convert {}", other.type_name())),
+            }
+        }
+        "string" => {
+            arity(1)?;
+            match &args[0] {
+                Value::String(s) => Ok(Value::String(s.clone())),
+                Value::Null | Value::List(_) | Value::Map(_) => Err(format!("string() cannot convert {}", args[0].type_name())),
+                other => Ok(Value::String(other.to_string())),
+            }
+        }
+        "bool" => {
+            arity(1)?;
+            match &args[0] {
+                Value::Bool(b) => Ok(Value::Bool(*b)),
+                Value::String(s) if s == "true" => Ok(Value::Bool(true)),
+                Value::String(s) if s == "false" => Ok(Value::Bool(false)),
+                other => Err(format!("bool() cannot convert {}", other)),
+            }
+        }
+        "size" => {
+            arity(1)?;
+            match &args[0] {
+                Value::String(s) => Ok(Value::Int(s.chars().count() as i64)),
+                Value::List(items) => Ok(Value::Int(items.len() as i64)),
+                Value::Map(entries) => Ok(Value::Int(entries.len() as i64)),
+                other => Err(format!("size() cannot measure {}", other.type_name())),
+            }
+        }
+        "has" => {
+            arity(1)?;
+            Ok(Value::Bool(args[0] != Value::Null))
+        }
+        "url" => {
+            arity(1)?;
+            let Value::String(s) = &args[0] else {
+                return Err(format!("url() needs a string, got {}", args[0].type_name()));
+            };
+            let parsed = url::Url::parse(s).map_err(|e| format!("url() cannot parse {:?}: {}", s, e))?;
+            let optional = |value: Option<&str>| value.map_or(Value::Null, |v| Value::String(v.to_string()));
+            Ok(Value::Map(BTreeMap::from([
+                ("scheme".to_string(), Value::String(parsed.scheme().to_string())),
+                ("host".to_string(), optional(parsed.host_str())),
+                ("port".to_string(), parsed.port_or_known_default().map_or(Value::Null, |p| Value::Int(p.into()))),
+                ("user".to_string(), Value::String(parsed.username().to_string())),
+                ("path".to_string(), Value::String(parsed.path().to_string())),
+                ("query".to_string(), optional(parsed.query())),
+            ])))
+        }
+        "matches" | "startsWith" | "endsWith" | "contains" => {
+            arity(2)?;
+            let (Value::String(s), Value::String(arg)) = (&args[0], &args[1]) else {
+                return Err(format!(
+                    "{}() needs strings, got {} and {}",
+                    name,
+                    args[0].type_name(),
+                    args[1].type_name()
+                ));
+            };
+            Ok(Value::Bool(match name {
+                "matches" => regex::RegexBuilder::new(arg)
+                    .size_limit(MAX_REGEX_SIZE)
+                    .build()
+                    .map_err(|e| format!("matches() has an invalid pattern: {}", e))?
+                    .is_match(s),
+                "startsWith" => s.starts_with(arg.as_str()),
+                "endsWith" => s.ends_with(arg.as_str()),
+                _ => s.contains(arg.as_str()),
+            }))
+        }
+        _ => Err(format!("unknown function '{}'", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_str(source: &str, bindings: &[(&str, Value)]) -> Result<Value, String> {
+        let bindings = bindings.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
+        Expression::parse(source)?.evaluate(&bindings)
+    }
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(eval_str("1 + 2 * 3", &[]), Ok(Value::Int(7)));
+        assert_eq!(eval_str("10 - 4 - 3", &[]), Ok(Value::Int(3)));
+        assert_eq!(eval_str("-(2 + 3) % 4", &[]), Ok(Value::Int(-1)));
+        assert_eq!(eval_str("true || false && false", &[]), Ok(Value::Bool(true)));
+        assert_eq!(eval_str("1 < 2 ? \"a\" : \"b\"", &[]), Ok(string("a")));
+    }
+
+    #[test]
+    fn bindings_members_and_functions() {
+        let resource = Value::Map(BTreeMap::from([("name".to_string(), string("web"))]));
+        let bindings = [("URL", string("https://db.internal:5432/app")), ("resource", resource)];
+        assert_eq!(eval_str("url(URL).port == 5432", &bindings), Ok(Value::Bool(true)));
+        assert_eq!(eval_str("resource.name.startsWith(\"we\")", &bindings), Ok(Value::Bool(true)));
+        assert_eq!(eval_str("resource[\"name\"] in [\"api\", \"web\"]", &bindings), Ok(Value::Bool(true)));
+        assert_eq!(eval_str("UNSET == null", &bindings), Ok(Value::Bool(true)));
+        assert_eq!(
+            Expression::parse("B && A || B").unwrap().identifiers(),
+            vec!["B", "A"]
+        );
+    }
+
+    #[test]
+    fn evaluation_errors() {
+        assert_eq!(eval_str("1 / 0", &[]), Err("division by zero".to_string()));
+        assert_eq!(eval_str("\"a\" && true", &[]), Err("'&&' needs bools, got string".to_string()));
+        assert_eq!(eval_str("int(\"x\")", &[]), Err("int() cannot parse \"x\"".to_string()));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Expression::parse("1 +").unwrap_err(), "unexpected end of expression");
+        assert_eq!(Expression::parse("1 2").unwrap_err(), "unexpected '2' at column 3");
+        assert_eq!(Expression::parse("nope(1)").unwrap_err(), "unknown function 'nope' at column 1");
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected() {
+        let nested = format!("{}1{}", "(".repeat(MAX_DEPTH + 1), ")".repeat(MAX_DEPTH + 1));
+        assert!(Expression::parse(&nested).unwrap_err().contains("levels deep"));
+        assert!(Expression::parse(&"!".repeat(MAX_DEPTH + 1)).unwrap_err().contains("levels deep"));
+    }
+
+    #[test]
+    fn long_operator_chains_are_rejected() {
+        for op in ["||", "&&", "==", "+", "*"] {
+            let chain = vec!["X"; 20_000].join(&format!(" {} ", op));
+            assert!(Expression::parse(&chain).unwrap_err().contains("levels deep"), "{}", op);
+        }
+        let members = format!("X{}", ".a".repeat(20_000));
+        assert!(Expression::parse(&members).unwrap_err().contains("levels deep"));
+        let indexes = format!("X{}", "[0]".repeat(20_000));
+        assert!(Expression::parse(&indexes).unwrap_err().contains("levels deep"));
+    }
+
+    #[test]
+    fn chains_within_the_limit_evaluate() {
+        let chain = vec!["X == \"a\""; 100].join(" || ");
+        assert_eq!(eval_str(&chain, &[("X", string("a"))]), Ok(Value::Bool(true)));
+    }
+}
@@ -0,0 +1,233 @@
+use crate::k8s::K8sResource;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+/// Variables seen per application, with every value observed for each.
+type AppVars = BTreeMap<String, BTreeMap<String, Vec<String>>>;
+
+/// Builds a starter `envcheck.yaml` from the workloads rendered for each environment.
+///
+/// Variables set by every workload in an environment become that environment's
+/// `required_vars`; variables an application sets in all of its environments become
+/// its `additional_vars`; anything else that was seen becomes `optional_vars`.
+#[derive(Default)]
+pub struct StarterConfig {
+    sources: Vec<String>,
+    environments: Vec<(String, AppVars)>,
+    stripped_names: bool,
+}
+
+impl StarterConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_overlay(&mut self, source: &str, environment: &str, resources: &[K8sResource]) {
+        self.sources.push(source.to_string());
+        let apps = match self.environments.iter().position(|(name, _)| name == environment) {
+            Some(i) => &mut self.environments[i].1,
+            None => {
+                self.environments.push((environment.to_string(), AppVars::new()));
+                &mut self.environments.last_mut().expect("just pushed").1
+            }
+        };
+
+        for resource in resources {
+            self.stripped_names |= resource.base_name != resource.name;
+            let vars = apps.entry(resource.base_name.clone()).or_default();
+            for container in &resource.containers {
+                for (name, value) in &container.env_vars {
+                    vars.entry(name.clone()).or_default().push(value.clone());
+                }
+            }
+        }
+    }
+
+    pub fn application_count(&self) -> usize {
+        self.environments
+            .iter()
+            .flat_map(|(_, apps)| apps.keys())
+            .collect::<BTreeSet<_>>()
+            .len()
+    }
+
+    pub fn render(&self) -> String {
+        let required: BTreeMap<&str, BTreeSet<&str>> = self
+            .environments
+            .iter()
+            .map(|(env, apps)| (env.as_str(), intersection(apps.values().map(|vars| var_names(vars)))))
+            .collect();
+
+        let mut app_envs: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (env, apps) in &self.environments {
+            for app in apps.keys() {
+                app_envs.entry(app.as_str()).or_default().push(env.as_str());
+            }
+        }
+
+        // Required in every environment the application runs in, and not already
+        // required by one of those environments.
+        let additional: BTreeMap<&str, BTreeSet<&str>> = app_envs
+            .iter()
+            .map(|(app, envs)| {
+                let set_everywhere = intersection(envs.iter().map(|env| var_names(&self.apps(env)[*app])));
+                let covered: BTreeSet<&str> = envs.iter().flat_map(|env| required[env].iter().copied()).collect();
+                (*app, set_everywhere.difference(&covered).copied().collect())
+            })
+            .collect();
+
+        let mut out = String::new();
+        let _ = writeln!(out, "# Generated by `kustomize-envcheck init` from:");
+        for source in &self.sources {
+            let _ = writeln!(out, "#   {}", source);
+        }
+        let _ = writeln!(out, "# Descriptions are placeholders and patterns are inferred from the values");
+        let _ = writeln!(out, "# currently in the manifests; review every entry before relying on it.");
+        let _ = writeln!(out);
+
+        if self.stripped_names {
+            let _ = writeln!(out, "# Workload names carry a namePrefix/nameSuffix; match applications without it.");
+            let _ = writeln!(out, "app_identity:");
+            let _ = writeln!(out, "  by: stripped-name");
+            let _ = writeln!(out);
+        }
+
+        let _ = writeln!(out, "environments:");
+        for (env, apps) in &self.environments {
+            let seen: BTreeSet<&str> = apps.values().flat_map(var_names).collect();
+            let optional: BTreeSet<&str> = seen
+                .iter()
+                .copied()
+                .filter(|var| !required[env.as_str()].contains(var))
+                .filter(|var| !apps.keys().any(|app| additional[app.as_str()].contains(var)))
+                .collect();
+
+            let _ = writeln!(out, "  {}:", key(env));
+            let env_values = |var: &str| -> Vec<&str> {
+                apps.values()
+                    .filter_map(|vars| vars.get(var))
+                    .flatten()
+                    .map(String::as_str)
+                    .collect()
+            };
+            let _ = writeln!(out, "    # Set by every workload in {}", env);
+            self.write_vars(&mut out, "    required_vars", &required[env.as_str()], |var| (None, env_values(var)));
+            self.write_vars(&mut out, "    optional_vars", &optional, |var| {
+                let users: Vec<&str> = apps.iter().filter(|(_, vars)| vars.contains_key(var)).map(|(app, _)| app.as_str()).collect();
+                (Some(format!("set by {}", users.join(", "))), env_values(var))
+            });
+        }
+
+        let _ = writeln!(out);
+        let _ = writeln!(out, "applications:");
+        for (app, envs) in &app_envs {
+            let _ = writeln!(out, "  {}:", key(app));
+            let envs_list: Vec<String> = envs.iter().map(|env| quote(env)).collect();
+            let _ = writeln!(out, "    environments: [{}]", envs_list.join(", "));
+            if additional[app].is_empty() {
+                continue;
+            }
+            let _ = writeln!(out, "    # Set by {} in every environment it runs in", app);
+            self.write_vars(&mut out, "    additional_vars", &additional[app], |var| {
+                let values = envs
+                    .iter()
+                    .filter_map(|env| self.apps(env)[*app].get(var))
+                    .flatten()
+                    .map(String::as_str)
+                    .collect();
+                (None, values)
+            });
+        }
+
+        out
+    }
+
+    fn apps(&self, environment: &str) -> &AppVars {
+        &self
+            .environments
+            .iter()
+            .find(|(name, _)| name == environment)
+            .expect("environment was collected")
+            .1
+    }
+
+    fn write_vars<'a>(
+        &self,
+        out: &mut String,
+        key: &str,
+        vars: &BTreeSet<&'a str>,
+        describe: impl Fn(&'a str) -> (Option<String>, Vec<&'a str>),
+    ) {
+        if vars.is_empty() {
+            let _ = writeln!(out, "{}: []", key);
+            return;
+        }
+        let _ = writeln!(out, "{}:", key);
+        let indent = " ".repeat(key.len() - key.trim_start().len());
+        for var in vars {
+            let (comment, values) = describe(var);
+            if let Some(comment) = comment {
+                let _ = writeln!(out, "{}  # {}", indent, comment);
+            }
+            let _ = writeln!(out, "{}  - name: {}", indent, quote(var));
+            let _ = writeln!(out, "{}    description: \"TODO\"", indent);
+            if let Some((pattern, kind)) = infer_pattern(&values) {
+                let _ = writeln!(out, "{}    pattern: {}  # inferred: {}", indent, quote(&pattern), kind);
+            }
+        }
+    }
+}
+
+fn var_names(vars: &BTreeMap<String, Vec<String>>) -> BTreeSet<&str> {
+    vars.keys().map(String::as_str).collect()
+}
+
+fn intersection<'a>(mut sets: impl Iterator<Item = BTreeSet<&'a str>>) -> BTreeSet<&'a str> {
+    let first = sets.next().unwrap_or_default();
+    sets.fold(first, |acc, set| acc.intersection(&set).copied().collect())
+}
+
+/// Proposes a pattern when every observed literal value has the same shape.
+/// Values taken from Secrets, ConfigMaps or fields are never inspected.
+fn infer_pattern(values: &[&str]) -> Option<(String, &'static str)> {
+    let from_reference = |v: &&str| {
+        ["Secret[", "ConfigMap[", "Field[", "Resource["].iter().any(|p| v.starts_with(p)) && v.ends_with(']')
+    };
+    if values.is_empty() || values.iter().any(from_reference) {
+        return None;
+    }
+
+    if values.iter().all(|v| !v.is_empty() && v.bytes().all(|b| b.is_ascii_digit())) {
+        return Some(("^[0-9]+$".to_string(), "integer"));
+    }
+    if values.iter().all(|v| *v == "true" || *v == "false") {
+        return Some(("^(true|false)$".to_string(), "boolean"));
+    }
+    let scheme = |v: &str| -> Option<String> {
+        let (scheme, rest) = v.split_once("://")?;
+        let valid = !scheme.is_empty() && !rest.is_empty() && scheme.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'+');
+        valid.then(|| scheme.to_string())
+    };
+    let first = scheme(values[0])?;
+    if values.iter().all(|v| scheme(v).as_deref() == Some(first.as_str())) {
+        return Some((format!("^{}://", regex::escape(&first)), "URL"));
+    }
+    None
+}
+
+/// Mapping key, left plain when YAML would not read it as anything but a string.
+fn key(name: &str) -> String {
+    let plain = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && !["true", "false", "yes", "no", "on", "off", "null", "y", "n"].contains(&name.to_ascii_lowercase().as_str());
+    if plain {
+        name.to_string()
+    } else {
+        quote(name)
+    }
+}
+
+/// Double-quoted YAML scalar; JSON string syntax is valid YAML.
+fn quote(value: &str) -> String {
+    serde_json::to_string(value).expect("strings always serialize")
+}
@@ -1,102 +1,250 @@
-use anyhow::Result;
-use k8s_openapi::api::apps::v1::{DaemonSet, Deployment, StatefulSet};
-use k8s_openapi::api::core::v1::{Container, EnvVar};
-use serde::Deserialize;
+use crate::error::Result;
+use crate::expansion;
+use k8s_openapi::api::core::v1::{Container, EnvVar, PodSpec, Probe};
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use serde::{Deserialize, Serialize};
 use serde_yaml::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
+const ORIGIN_ANNOTATION: &str = "config.kubernetes.io/origin";
+const TRANSFORMATIONS_ANNOTATION: &str = "alpha.config.kubernetes.io/transformations";
+/// Kinds checked without configuration: API group (`None` to match the kind in
+/// any group, or without an `apiVersion`), kind and the path of the pod spec.
+const BUILTIN_WORKLOAD_KINDS: &[(Option<&str>, &str, &str)] = &[
+    (None, "Deployment", "spec.template.spec"),
+    (None, "StatefulSet", "spec.template.spec"),
+    (None, "DaemonSet", "spec.template.spec"),
+    (Some("argoproj.io"), "Rollout", "spec.template.spec"),
+    (Some("serving.knative.dev"), "Service", "spec.template.spec"),
+];
+
+/// Comma-separated variables whose findings are suppressed for the workload.
+pub const IGNORE_ANNOTATION: &str = "envcheck.io/ignore";
+/// `"true"` suppresses every finding for the workload.
+pub const SKIP_ANNOTATION: &str = "envcheck.io/skip";
+
+/// A workload with a pod template, such as a Deployment, Argo Rollout, Knative
+/// Service or a kind configured under `workload_kinds`, and the variables of its containers.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct K8sResource {
     pub kind: String,
     pub name: String,
+    pub namespace: Option<String>,
+    /// `name` with any kustomize namePrefix/nameSuffix of the built overlay removed.
+    pub base_name: String,
+    pub labels: BTreeMap<String, String>,
+    pub annotations: BTreeMap<String, String>,
     pub containers: Vec<ContainerInfo>,
+    /// File the resource was loaded from, relative to the built overlay, as
+    /// recorded by kustomize's `originAnnotations` build metadata.
+    pub origin_path: Option<String>,
+    /// Kustomization files whose patches modified the resource, relative to the
+    /// built overlay, as recorded by `transformerAnnotations`.
+    pub patched_in: Vec<String>,
+    /// Findings the workload's annotations ask to suppress.
+    pub suppressions: Suppressions,
+}
+
+/// Read from the `envcheck.io/ignore` and `envcheck.io/skip` annotations.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct Suppressions {
+    pub ignored_vars: Vec<String>,
+    pub skip: bool,
 }
 
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct ContainerInfo {
     pub name: String,
+    /// Value of each variable as the container sees it, with `$(VAR)` references
+    /// to earlier literal values expanded; `valueFrom` sources are described.
     pub env_vars: HashMap<String, String>,
+    /// Entries under `env` as written, in order.
+    pub env: Vec<EnvEntry>,
     pub env_from_refs: Vec<String>,
+    /// Keys imported through `envFrom` from ConfigMaps and Secrets in the same
+    /// manifests, described like `valueFrom` sources, e.g. `ConfigMap[app-config:KEY]`.
+    pub env_from_keys: HashMap<String, String>,
+    /// Where each variable in `env_vars` was introduced or last modified.
+    pub env_origins: HashMap<String, Origin>,
+    pub command: Vec<String>,
+    pub args: Vec<String>,
+    /// Every `ports[].containerPort`.
+    pub ports: Vec<i32>,
+    pub probe_ports: ProbePorts,
+}
+
+/// Ports a container's probes target, with named ports resolved.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct ProbePorts {
+    pub readiness: Option<i32>,
+    pub liveness: Option<i32>,
+    pub startup: Option<i32>,
+}
+
+/// An entry under a container's `env`.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct EnvEntry {
+    pub name: String,
+    /// The literal `value`, or `None` for `valueFrom`.
+    pub value: Option<String>,
+    /// Description of the `valueFrom` source, e.g. `Secret[name:key]`.
+    pub value_from: Option<String>,
+}
+
+/// Data keys of the ConfigMaps and Secrets in rendered manifests, keyed by kind,
+/// namespace and name.
+type EnvSources = HashMap<(String, String, String), Vec<String>>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Origin {
+    pub file: String,
+    pub line: usize,
+    pub overlay: String,
+}
+
+impl K8sResource {
+    /// Namespace used for matching config rules; workloads without one land in `default`.
+    pub fn effective_namespace(&self) -> &str {
+        self.namespace.as_deref().unwrap_or("default")
+    }
 }
 
-pub struct K8sParser;
+/// Extracts workloads from rendered multi-document YAML.
+#[derive(Debug, Default)]
+pub struct K8sParser {
+    workload_kinds: HashMap<String, String>,
+}
 
 impl K8sParser {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// Also extracts workloads of custom kinds, mapping `apiVersion/kind` to the
+    /// dot-separated path of the pod spec, as under `workload_kinds` in the configuration.
+    pub fn with_workload_kinds(mut self, workload_kinds: HashMap<String, String>) -> Self {
+        self.workload_kinds = workload_kinds;
+        self
     }
 
     pub fn parse_yaml(&self, yaml_content: &str) -> Result<Vec<K8sResource>> {
         let mut resources = Vec::new();
+        let documents = serde_yaml::Deserializer::from_str(yaml_content)
+            .map(Value::deserialize)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let sources = self.collect_env_sources(&documents);
 
-        for document in serde_yaml::Deserializer::from_str(yaml_content) {
-            let value = Value::deserialize(document)?;
-            
-            if let Some(kind) = value.get("kind").and_then(|k| k.as_str()) {
-                match kind {
-                    "Deployment" => {
-                        if let Ok(deployment) = serde_yaml::from_value::<Deployment>(value.clone()) {
-                            if let Some(resource) = self.extract_from_deployment(&deployment) {
-                                resources.push(resource);
-                            }
-                        }
-                    }
-                    "StatefulSet" => {
-                        if let Ok(statefulset) = serde_yaml::from_value::<StatefulSet>(value.clone()) {
-                            if let Some(resource) = self.extract_from_statefulset(&statefulset) {
-                                resources.push(resource);
-                            }
-                        }
-                    }
-                    "DaemonSet" => {
-                        if let Ok(daemonset) = serde_yaml::from_value::<DaemonSet>(value.clone()) {
-                            if let Some(resource) = self.extract_from_daemonset(&daemonset) {
-                                resources.push(resource);
-                            }
-                        }
-                    }
-                    _ => {}
-                }
+        for document in &documents {
+            let Some(kind) = document.get("kind").and_then(|k| k.as_str()) else {
+                continue;
+            };
+            let api_version = document.get("apiVersion").and_then(|v| v.as_str()).unwrap_or_default();
+            let Some(path) = pod_spec_path(&self.workload_kinds, api_version, kind) else {
+                continue;
+            };
+            if let Some(resource) = self.extract_workload(kind, document, &path, &sources) {
+                resources.push(resource);
             }
         }
 
         Ok(resources)
     }
 
-    fn extract_from_deployment(&self, deployment: &Deployment) -> Option<K8sResource> {
-        let name = deployment.metadata.name.clone()?;
-        let containers = self.extract_containers(&deployment.spec.as_ref()?.template.spec.as_ref()?.containers);
+    fn collect_env_sources(&self, documents: &[Value]) -> EnvSources {
+        let mut sources = EnvSources::new();
+        for document in documents {
+            let Some(kind @ ("ConfigMap" | "Secret")) = document.get("kind").and_then(|k| k.as_str()) else {
+                continue;
+            };
+            let metadata = document.get("metadata");
+            let Some(name) = metadata.and_then(|m| m.get("name")).and_then(|n| n.as_str()) else {
+                continue;
+            };
+            let namespace = metadata.and_then(|m| m.get("namespace")).and_then(|n| n.as_str()).unwrap_or("default");
+            let keys = ["data", "stringData", "binaryData"]
+                .iter()
+                .filter_map(|field| document.get(field).and_then(|d| d.as_mapping()))
+                .flat_map(|data| data.keys().filter_map(|k| k.as_str().map(str::to_string)))
+                .collect();
+            sources.insert((kind.to_string(), namespace.to_string(), name.to_string()), keys);
+        }
+        sources
+    }
+
+    fn extract_workload(&self, kind: &str, document: &Value, path: &[String], sources: &EnvSources) -> Option<K8sResource> {
+        let metadata: ObjectMeta = serde_yaml::from_value(document.get("metadata")?.clone()).ok()?;
+        let name = metadata.name.clone()?;
+        let mut pod_spec = path.iter().try_fold(document, |value, key| value.get(key.as_str()))?.clone();
+        name_containers(&mut pod_spec);
+        let pod_spec: PodSpec = serde_yaml::from_value(pod_spec).ok()?;
+
+        let namespace = metadata.namespace.as_deref().unwrap_or("default");
+        let containers = self.extract_containers(&pod_spec.containers, namespace, sources);
+        let (origin_path, patched_in) = self.extract_provenance(&metadata);
+        let suppressions = self.extract_suppressions(&metadata);
 
         Some(K8sResource {
-            kind: "Deployment".to_string(),
+            kind: kind.to_string(),
+            base_name: name.clone(),
             name,
+            namespace: metadata.namespace.clone(),
+            labels: metadata.labels.clone().unwrap_or_default(),
+            annotations: metadata.annotations.clone().unwrap_or_default(),
             containers,
+            origin_path,
+            patched_in,
+            suppressions,
         })
     }
 
-    fn extract_from_statefulset(&self, statefulset: &StatefulSet) -> Option<K8sResource> {
-        let name = statefulset.metadata.name.clone()?;
-        let containers = self.extract_containers(&statefulset.spec.as_ref()?.template.spec.as_ref()?.containers);
+    fn extract_suppressions(&self, metadata: &ObjectMeta) -> Suppressions {
+        let Some(annotations) = &metadata.annotations else {
+            return Suppressions::default();
+        };
 
-        Some(K8sResource {
-            kind: "StatefulSet".to_string(),
-            name,
-            containers,
-        })
+        Suppressions {
+            ignored_vars: annotations
+                .get(IGNORE_ANNOTATION)
+                .map(|list| list.split(',').map(str::trim).filter(|v| !v.is_empty()).map(str::to_string).collect())
+                .unwrap_or_default(),
+            skip: annotations.get(SKIP_ANNOTATION).is_some_and(|v| v.trim().eq_ignore_ascii_case("true")),
+        }
     }
 
-    fn extract_from_daemonset(&self, daemonset: &DaemonSet) -> Option<K8sResource> {
-        let name = daemonset.metadata.name.clone()?;
-        let containers = self.extract_containers(&daemonset.spec.as_ref()?.template.spec.as_ref()?.containers);
+    fn extract_provenance(&self, metadata: &ObjectMeta) -> (Option<String>, Vec<String>) {
+        let Some(annotations) = &metadata.annotations else {
+            return (None, Vec::new());
+        };
 
-        Some(K8sResource {
-            kind: "DaemonSet".to_string(),
-            name,
-            containers,
-        })
+        let origin_path = annotations
+            .get(ORIGIN_ANNOTATION)
+            .and_then(|origin| serde_yaml::from_str::<Value>(origin).ok())
+            .and_then(|origin| origin.get("path").and_then(|p| p.as_str()).map(str::to_string));
+
+        let patched_in = annotations
+            .get(TRANSFORMATIONS_ANNOTATION)
+            .and_then(|t| serde_yaml::from_str::<Vec<Value>>(t).ok())
+            .unwrap_or_default()
+            .iter()
+            .filter(|t| {
+                t.get("configuredBy")
+                    .and_then(|b| b.get("kind"))
+                    .and_then(|k| k.as_str())
+                    .is_some_and(|kind| kind.starts_with("Patch"))
+            })
+            .filter_map(|t| t.get("configuredIn").and_then(|c| c.as_str()).map(str::to_string))
+            .collect();
+
+        (origin_path, patched_in)
     }
 
-    fn extract_containers(&self, containers: &[Container]) -> Vec<ContainerInfo> {
+    fn extract_containers(&self, containers: &[Container], namespace: &str, sources: &EnvSources) -> Vec<ContainerInfo> {
         containers
             .iter()
             .map(|container| {
@@ -106,20 +254,64 @@ impl K8sParser {
                 ContainerInfo {
                     name: container.name.clone(),
                     env_vars,
+                    env: container
+                        .env
+                        .iter()
+                        .flatten()
+                        .map(|var| EnvEntry {
+                            name: var.name.clone(),
+                            value: var.value.clone(),
+                            value_from: var.value_from.as_ref().map(|v| self.describe_value_from(v)),
+                        })
+                        .collect(),
                     env_from_refs,
+                    env_from_keys: self.extract_env_from_keys(&container.env_from, namespace, sources),
+                    env_origins: HashMap::new(),
+                    command: container.command.clone().unwrap_or_default(),
+                    args: container.args.clone().unwrap_or_default(),
+                    ports: container.ports.iter().flatten().map(|p| p.container_port).collect(),
+                    probe_ports: ProbePorts {
+                        readiness: self.probe_port(container, &container.readiness_probe),
+                        liveness: self.probe_port(container, &container.liveness_probe),
+                        startup: self.probe_port(container, &container.startup_probe),
+                    },
                 }
             })
             .collect()
     }
 
+    fn probe_port(&self, container: &Container, probe: &Option<Probe>) -> Option<i32> {
+        let probe = probe.as_ref()?;
+        let port = probe
+            .http_get
+            .as_ref()
+            .map(|action| &action.port)
+            .or_else(|| probe.tcp_socket.as_ref().map(|action| &action.port));
+        match port {
+            Some(IntOrString::Int(port)) => Some(*port),
+            Some(IntOrString::String(name)) => container
+                .ports
+                .iter()
+                .flatten()
+                .find(|p| p.name.as_ref() == Some(name))
+                .map(|p| p.container_port),
+            None => probe.grpc.as_ref().map(|action| action.port),
+        }
+    }
+
     fn extract_env_vars(&self, env_vars: &Option<Vec<EnvVar>>) -> HashMap<String, String> {
         let mut map = HashMap::new();
+        // Literal values defined so far, which later values may reference.
+        let mut literals: HashMap<&str, String> = HashMap::new();
 
         if let Some(vars) = env_vars {
             for var in vars {
                 if let Some(value) = &var.value {
-                    map.insert(var.name.clone(), value.clone());
+                    let expanded = expansion::expand(value, |name| literals.get(name).map(String::as_str));
+                    map.insert(var.name.clone(), expanded.clone());
+                    literals.insert(&var.name, expanded);
                 } else if let Some(value_from) = &var.value_from {
+                    literals.remove(var.name.as_str());
                     let source_description = self.describe_value_from(value_from);
                     map.insert(var.name.clone(), source_description);
                 }
@@ -143,6 +335,37 @@ impl K8sParser {
         }
     }
 
+    fn extract_env_from_keys(
+        &self,
+        env_from: &Option<Vec<k8s_openapi::api::core::v1::EnvFromSource>>,
+        namespace: &str,
+        sources: &EnvSources,
+    ) -> HashMap<String, String> {
+        let mut keys = HashMap::new();
+
+        for source in env_from.iter().flatten() {
+            let refs = [
+                ("ConfigMap", source.config_map_ref.as_ref().map(|r| &r.name)),
+                ("Secret", source.secret_ref.as_ref().map(|r| &r.name)),
+            ];
+            for (kind, name) in refs {
+                let Some(name) = name else {
+                    continue;
+                };
+                let Some(data_keys) = sources.get(&(kind.to_string(), namespace.to_string(), name.clone())) else {
+                    continue;
+                };
+                // Later sources win, as in Kubernetes.
+                for key in data_keys {
+                    let prefixed = format!("{}{}", source.prefix.as_deref().unwrap_or(""), key);
+                    keys.insert(prefixed, format!("{}[{}:{}]", kind, name, key));
+                }
+            }
+        }
+
+        keys
+    }
+
     fn extract_env_from(&self, env_from: &Option<Vec<k8s_openapi::api::core::v1::EnvFromSource>>) -> Vec<String> {
         let mut refs = Vec::new();
 
@@ -159,4 +382,41 @@ impl K8sParser {
 
         refs
     }
-}
\ No newline at end of file
+}
+
+/// Path of the pod spec in workloads of this kind, from `workload_kinds` (keyed
+/// by `apiVersion/kind` as in the configuration) or the built-in kinds; `None`
+/// for kinds that are not workloads.
+pub(crate) fn pod_spec_path(workload_kinds: &HashMap<String, String>, api_version: &str, kind: &str) -> Option<Vec<String>> {
+    if let Some(path) = workload_kinds.get(&format!("{}/{}", api_version, kind)) {
+        return Some(split_path(path));
+    }
+    let group = api_version.rsplit_once('/').map_or("", |(group, _)| group);
+    BUILTIN_WORKLOAD_KINDS
+        .iter()
+        .find(|(builtin_group, builtin_kind, _)| {
+            *builtin_kind == kind && builtin_group.is_none_or(|builtin_group| builtin_group == group)
+        })
+        .map(|(_, _, path)| split_path(path))
+}
+
+/// Splits a pod spec path such as `spec.template.spec`; a leading `$.` or `.` is
+/// accepted, as in JSONPath.
+pub(crate) fn split_path(path: &str) -> Vec<String> {
+    let path = path.strip_prefix('$').unwrap_or(path);
+    path.split('.').filter(|key| !key.is_empty()).map(str::to_string).collect()
+}
+
+/// Names unnamed containers, which Knative allows, after their position.
+fn name_containers(pod_spec: &mut Value) {
+    let Some(containers) = pod_spec.get_mut("containers").and_then(|c| c.as_sequence_mut()) else {
+        return;
+    };
+    for (i, container) in containers.iter_mut().enumerate() {
+        if let Some(container) = container.as_mapping_mut() {
+            if !container.contains_key("name") {
+                container.insert(Value::from("name"), Value::from(format!("container-{}", i)));
+            }
+        }
+    }
+}
@@ -1,42 +1,401 @@
-use anyhow::{Context, Result};
-use std::path::Path;
-use std::process::Command;
+use crate::cache::BuildCache;
+use crate::error::{Error, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 
-pub struct KustomizeBuilder;
+pub const DEFAULT_TIMEOUT_SECS: u64 = 300;
+pub const DEFAULT_JOBS: usize = 4;
+
+/// How overlays are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Renderer {
+    /// Run the kustomize binary
+    Kustomize,
+    /// Use the built-in renderer for a subset of kustomize
+    #[cfg(feature = "builtin-kustomize")]
+    Builtin,
+}
+
+/// Why an overlay could not be rendered.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum KustomizeError {
+    Spawn {
+        overlay: PathBuf,
+        source: std::io::Error,
+    },
+    Failed {
+        overlay: PathBuf,
+        code: Option<i32>,
+        stderr: String,
+    },
+    TimedOut {
+        overlay: PathBuf,
+        timeout: Duration,
+    },
+    InvalidOutput {
+        overlay: PathBuf,
+    },
+    #[cfg(feature = "builtin-kustomize")]
+    Render {
+        overlay: PathBuf,
+        message: String,
+    },
+}
+
+impl fmt::Display for KustomizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KustomizeError::Spawn { overlay, .. } => {
+                write!(f, "Failed to execute kustomize build for {}", overlay.display())
+            }
+            KustomizeError::Failed { overlay, code, stderr } => {
+                let code = code.map_or_else(|| "signal".to_string(), |c| c.to_string());
+                write!(f, "Kustomize build failed for {} (exit {}): {}", overlay.display(), code, stderr.trim())
+            }
+            KustomizeError::TimedOut { overlay, timeout } => {
+                write!(f, "Kustomize build for {} timed out after {}s", overlay.display(), timeout.as_secs())
+            }
+            KustomizeError::InvalidOutput { overlay } => {
+                write!(f, "Failed to parse kustomize output for {} as UTF-8", overlay.display())
+            }
+            #[cfg(feature = "builtin-kustomize")]
+            KustomizeError::Render { overlay, message } => {
+                write!(f, "Built-in renderer failed for {}: {}", overlay.display(), message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for KustomizeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            KustomizeError::Spawn { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Renders overlays with kustomize or the built-in renderer, optionally through a [`BuildCache`].
+#[derive(Debug, Clone)]
+pub struct KustomizeBuilder {
+    timeout: Duration,
+    jobs: usize,
+    cache: Option<Arc<BuildCache>>,
+    renderer: Renderer,
+    workload_kinds: HashMap<String, String>,
+    /// The kustomize executable, looked up on `PATH` unless a test replaces it.
+    program: PathBuf,
+}
+
+impl Default for KustomizeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl KustomizeBuilder {
     pub fn new() -> Self {
-        Self
+        Self {
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            jobs: DEFAULT_JOBS,
+            cache: None,
+            renderer: Renderer::Kustomize,
+            workload_kinds: HashMap::new(),
+            program: PathBuf::from("kustomize"),
+        }
     }
 
-    pub async fn build(&self, dir: &Path) -> Result<String> {
-        let output = Command::new("kustomize")
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    pub fn with_cache(mut self, cache: BuildCache) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    pub fn with_renderer(mut self, renderer: Renderer) -> Self {
+        self.renderer = renderer;
+        self
+    }
+
+    /// Custom workload kinds whose pod templates the built-in renderer transforms,
+    /// as under `workload_kinds` in the configuration.
+    pub fn with_workload_kinds(mut self, workload_kinds: HashMap<String, String>) -> Self {
+        self.workload_kinds = workload_kinds;
+        self
+    }
+
+    /// Renders a single overlay, reusing cached output when none of the files it
+    /// references have changed since the last build.
+    pub async fn build(&self, dir: &Path) -> Result<String, KustomizeError> {
+        #[cfg(feature = "builtin-kustomize")]
+        if self.renderer == Renderer::Builtin {
+            return self.run_builtin(dir).await;
+        }
+
+        let Some(cache) = &self.cache else {
+            return self.run_build(dir).await;
+        };
+
+        let key = {
+            let cache = Arc::clone(cache);
+            let dir = dir.to_path_buf();
+            tokio::task::spawn_blocking(move || cache.key(&dir)).await.ok().flatten()
+        };
+        let Some(key) = key else {
+            return self.run_build(dir).await;
+        };
+
+        if let Some(rendered) = cache.get(&key) {
+            return Ok(rendered);
+        }
+
+        let rendered = self.run_build(dir).await?;
+        // A cache that cannot be written only costs a rebuild next time.
+        let _ = cache.put(&key, &rendered);
+        Ok(rendered)
+    }
+
+    #[cfg(feature = "builtin-kustomize")]
+    async fn run_builtin(&self, dir: &Path) -> Result<String, KustomizeError> {
+        let overlay = dir.to_path_buf();
+        let renderer = crate::kustomize_builtin::BuiltinRenderer::new().with_workload_kinds(self.workload_kinds.clone());
+        let render = tokio::task::spawn_blocking(move || renderer.render(&overlay));
+
+        let render_error = |message: String| KustomizeError::Render {
+            overlay: dir.to_path_buf(),
+            message,
+        };
+        match tokio::time::timeout(self.timeout, render).await {
+            Ok(Ok(result)) => result.map_err(|e| render_error(format!("{:#}", e))),
+            Ok(Err(join_error)) => Err(render_error(join_error.to_string())),
+            Err(_) => Err(KustomizeError::TimedOut {
+                overlay: dir.to_path_buf(),
+                timeout: self.timeout,
+            }),
+        }
+    }
+
+    /// Runs `kustomize build` for a single overlay. The child process is killed
+    /// if the timeout elapses or the returned future is dropped.
+    async fn run_build(&self, dir: &Path) -> Result<String, KustomizeError> {
+        let child = Command::new(&self.program)
             .arg("build")
             .arg(dir)
-            .output()
-            .context("Failed to execute kustomize build")?;
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|source| KustomizeError::Spawn {
+                overlay: dir.to_path_buf(),
+                source,
+            })?;
+
+        let output = match tokio::time::timeout(self.timeout, child.wait_with_output()).await {
+            Ok(result) => result.map_err(|source| KustomizeError::Spawn {
+                overlay: dir.to_path_buf(),
+                source,
+            })?,
+            Err(_) => {
+                return Err(KustomizeError::TimedOut {
+                    overlay: dir.to_path_buf(),
+                    timeout: self.timeout,
+                })
+            }
+        };
 
         if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("Kustomize build failed: {}", stderr);
+            return Err(KustomizeError::Failed {
+                overlay: dir.to_path_buf(),
+                code: output.status.code(),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            });
         }
 
-        let stdout = String::from_utf8(output.stdout)
-            .context("Failed to parse kustomize output as UTF-8")?;
+        String::from_utf8(output.stdout).map_err(|_| KustomizeError::InvalidOutput {
+            overlay: dir.to_path_buf(),
+        })
+    }
 
-        Ok(stdout)
+    /// Builds several overlays with at most `jobs` kustomize processes running at
+    /// once. Outputs are returned in the same order as `dirs`; the first failure
+    /// cancels the builds still in flight.
+    pub async fn build_all(&self, dirs: &[PathBuf]) -> Result<Vec<String>, KustomizeError> {
+        let semaphore = Arc::new(Semaphore::new(self.jobs));
+        let mut tasks = JoinSet::new();
+
+        for (index, dir) in dirs.iter().enumerate() {
+            let builder = self.clone();
+            let semaphore = Arc::clone(&semaphore);
+            let dir = dir.clone();
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire_owned().await.expect("semaphore is never closed");
+                (index, builder.build(&dir).await)
+            });
+        }
+
+        let mut outputs = vec![String::new(); dirs.len()];
+        while let Some(joined) = tasks.join_next().await {
+            let (index, result) = joined.expect("kustomize build task panicked");
+            outputs[index] = result?;
+        }
+
+        Ok(outputs)
     }
 
-    pub fn check_kustomize_installed() -> Result<()> {
+    /// Returns the version string reported by the installed kustomize binary.
+    pub async fn check_kustomize_installed() -> Result<String> {
         let output = Command::new("kustomize")
             .arg("version")
+            .stdin(Stdio::null())
             .output()
-            .context("Failed to check kustomize installation")?;
+            .await
+            .map_err(|e| Error::KustomizeNotInstalled { source: Some(e) })?;
 
         if !output.status.success() {
-            anyhow::bail!("Kustomize is not installed or not in PATH");
+            return Err(Error::KustomizeNotInstalled { source: None });
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+/// namePrefix/nameSuffix values declared anywhere in an overlay's kustomization
+/// tree, used to recover the name a workload had before kustomize renamed it.
+#[derive(Debug, Clone, Default)]
+pub struct NameAffixes {
+    prefixes: Vec<String>,
+    suffixes: Vec<String>,
+}
+
+impl NameAffixes {
+    pub fn collect(dir: &Path) -> Self {
+        let mut affixes = Self::default();
+        affixes.collect_from(dir, &mut Vec::new());
+        // Try longer affixes first so `prod-eu-` wins over `prod-`.
+        affixes.prefixes.sort_by_key(|p| std::cmp::Reverse(p.len()));
+        affixes.suffixes.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        affixes
+    }
+
+    fn collect_from(&mut self, dir: &Path, visited: &mut Vec<PathBuf>) {
+        let Ok(dir) = dir.canonicalize() else {
+            return;
+        };
+        if visited.contains(&dir) {
+            return;
+        }
+        visited.push(dir.clone());
+
+        let Some(kustomization) = ["kustomization.yaml", "kustomization.yml", "Kustomization"]
+            .iter()
+            .find_map(|name| std::fs::read_to_string(dir.join(name)).ok())
+            .and_then(|content| serde_yaml::from_str::<serde_yaml::Value>(&content).ok())
+        else {
+            return;
+        };
+
+        for (key, list) in [("namePrefix", &mut self.prefixes), ("nameSuffix", &mut self.suffixes)] {
+            if let Some(affix) = kustomization.get(key).and_then(|v| v.as_str()) {
+                if !affix.is_empty() && !list.iter().any(|a| a == affix) {
+                    list.push(affix.to_string());
+                }
+            }
+        }
+
+        for key in ["resources", "bases", "components"] {
+            let Some(references) = kustomization.get(key).and_then(|v| v.as_sequence()) else {
+                continue;
+            };
+            for reference in references.iter().filter_map(|r| r.as_str()) {
+                let path = dir.join(reference);
+                if path.is_dir() {
+                    self.collect_from(&path, visited);
+                }
+            }
         }
+    }
+
+    /// Repeatedly removes known prefixes and suffixes, since nested overlays
+    /// stack them (`prod-` + `team-` + `web-app`).
+    pub fn strip(&self, name: &str) -> String {
+        let mut stripped = name;
+        while let Some(rest) = self
+            .prefixes
+            .iter()
+            .find_map(|p| stripped.strip_prefix(p.as_str()).filter(|rest| !rest.is_empty()))
+        {
+            stripped = rest;
+        }
+        while let Some(rest) = self
+            .suffixes
+            .iter()
+            .find_map(|s| stripped.strip_suffix(s.as_str()).filter(|rest| !rest.is_empty()))
+        {
+            stripped = rest;
+        }
+        stripped.to_string()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    /// A builder running `script` as kustomize.
+    fn fake_kustomize(dir: &TempDir, script: &str) -> KustomizeBuilder {
+        let program = dir.path().join("kustomize");
+        std::fs::write(&program, format!("#!/bin/sh\n{}", script)).unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+        KustomizeBuilder {
+            program,
+            ..KustomizeBuilder::new()
+        }
+    }
+
+    #[tokio::test]
+    async fn timeout_kills_build_and_names_overlay() {
+        let dir = TempDir::new().unwrap();
+        let finished = dir.path().join("finished");
+        let builder = fake_kustomize(&dir, &format!("sleep 1\ntouch {}\n", finished.display()))
+            .with_timeout(Duration::from_millis(100));
+        let overlay = dir.path().join("overlays/prod");
+
+        let error = builder.build(&overlay).await.unwrap_err();
+        assert!(matches!(&error, KustomizeError::TimedOut { overlay: o, .. } if *o == overlay), "{:?}", error);
+        assert!(error.to_string().contains("overlays/prod"), "{}", error);
+
+        // A build left running would create the file after its sleep.
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(!finished.exists());
+    }
+
+    #[tokio::test]
+    async fn failed_build_reports_stderr() {
+        let dir = TempDir::new().unwrap();
+        let builder = fake_kustomize(&dir, "echo 'missing resource' >&2\nexit 3\n");
 
-        Ok(())
+        let error = builder.build(dir.path()).await.unwrap_err();
+        assert!(matches!(error, KustomizeError::Failed { code: Some(3), .. }), "{:?}", error);
+        assert!(error.to_string().ends_with("(exit 3): missing resource"), "{}", error);
     }
-}
\ No newline at end of file
+}
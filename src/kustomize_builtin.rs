@@ -0,0 +1,1340 @@
+//! In-process renderer for the subset of kustomize that matters to envcheck:
+//! enough to produce the workload env blocks K8sParser reads, on hosts where the
+//! kustomize binary is not available. Anything outside the subset is reported as
+//! an error instead of being silently ignored.
+
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+const KUSTOMIZATION_FILES: &[&str] = &["kustomization.yaml", "kustomization.yml", "Kustomization"];
+
+const SUPPORTED_FIELDS: &[&str] = &[
+    "apiVersion",
+    "kind",
+    "metadata",
+    "resources",
+    "bases",
+    "namePrefix",
+    "nameSuffix",
+    "namespace",
+    "commonLabels",
+    "commonAnnotations",
+    "configMapGenerator",
+    "secretGenerator",
+    "generatorOptions",
+    "patches",
+    "patchesStrategicMerge",
+    "patchesJson6902",
+    "images",
+    "buildMetadata",
+];
+
+const ORIGIN_ANNOTATION: &str = "config.kubernetes.io/origin";
+const TRANSFORMATIONS_ANNOTATION: &str = "alpha.config.kubernetes.io/transformations";
+
+/// Kinds whose pod template kustomize transforms besides the workload kinds
+/// envcheck checks, with the path of their pod spec.
+const OTHER_POD_KINDS: &[(&str, &str)] = &[
+    ("ReplicaSet", "spec.template.spec"),
+    ("Job", "spec.template.spec"),
+    ("CronJob", "spec.jobTemplate.spec.template.spec"),
+];
+
+const CLUSTER_SCOPED_KINDS: &[&str] = &[
+    "Namespace",
+    "ClusterRole",
+    "ClusterRoleBinding",
+    "CustomResourceDefinition",
+    "PersistentVolume",
+    "StorageClass",
+    "PriorityClass",
+    "MutatingWebhookConfiguration",
+    "ValidatingWebhookConfiguration",
+];
+
+#[derive(Debug, Clone)]
+struct Resource {
+    value: Value,
+    /// Name as first declared, so overlay patches can target base resources
+    /// after a base-level namePrefix/nameSuffix has been applied.
+    original_name: String,
+    /// Generated ConfigMaps/Secrets get a content hash suffix at the end of the build.
+    needs_hash: bool,
+    /// File the resource was loaded from, for `buildMetadata: [originAnnotations]`.
+    origin: Option<PathBuf>,
+    /// Patches applied to the resource, for `buildMetadata: [transformerAnnotations]`.
+    transformations: Vec<Transformation>,
+}
+
+#[derive(Debug, Clone)]
+struct Transformation {
+    configured_in: PathBuf,
+    transformer: &'static str,
+}
+
+impl Resource {
+    fn kind(&self) -> &str {
+        self.value.get("kind").and_then(|k| k.as_str()).unwrap_or_default()
+    }
+
+    fn name(&self) -> &str {
+        self.value
+            .pointer("/metadata/name")
+            .and_then(|n| n.as_str())
+            .unwrap_or_default()
+    }
+
+    fn set_name(&mut self, name: String) {
+        if let Some(metadata) = metadata_mut(&mut self.value) {
+            metadata.insert("name".to_string(), Value::String(name));
+        }
+    }
+
+    fn matches(&self, kind: &str, name: &str) -> bool {
+        self.kind() == kind && (self.name() == name || self.original_name == name)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeneratorOptions {
+    #[serde(default)]
+    disable_name_suffix_hash: bool,
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    #[serde(default)]
+    annotations: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Generator {
+    name: String,
+    #[serde(default)]
+    namespace: Option<String>,
+    #[serde(default)]
+    behavior: Option<String>,
+    #[serde(default)]
+    literals: Vec<String>,
+    #[serde(default)]
+    envs: Vec<String>,
+    #[serde(default)]
+    env: Option<String>,
+    #[serde(default)]
+    files: Vec<String>,
+    #[serde(default, rename = "type")]
+    secret_type: Option<String>,
+    #[serde(default)]
+    options: Option<GeneratorOptions>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PatchTarget {
+    #[serde(default)]
+    kind: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    namespace: Option<String>,
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default, rename = "labelSelector")]
+    label_selector: Option<String>,
+    #[serde(default, rename = "annotationSelector")]
+    annotation_selector: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Patch {
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    patch: Option<String>,
+    #[serde(default)]
+    target: Option<PatchTarget>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageOverride {
+    name: String,
+    #[serde(default)]
+    new_name: Option<String>,
+    #[serde(default)]
+    new_tag: Option<String>,
+    #[serde(default)]
+    digest: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct BuiltinRenderer {
+    workload_kinds: HashMap<String, String>,
+}
+
+impl BuiltinRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also transforms the pod templates of custom kinds, mapping `apiVersion/kind`
+    /// to the pod spec path as under `workload_kinds` in the configuration.
+    pub fn with_workload_kinds(mut self, workload_kinds: HashMap<String, String>) -> Self {
+        self.workload_kinds = workload_kinds;
+        self
+    }
+
+    /// Renders the kustomization in `dir` into a multi-document YAML string.
+    pub fn render(&self, dir: &Path) -> Result<String> {
+        let mut resources = self.render_dir(dir, &mut Vec::new())?;
+        apply_name_hashes(&mut resources, &self.workload_kinds)?;
+
+        let root = dir.canonicalize()?;
+        let (_, kustomization) = read_kustomization(&root)?;
+        let build_metadata = string_list(&kustomization, "buildMetadata");
+        for resource in resources.iter_mut() {
+            annotate_build_metadata(resource, &root, &build_metadata)?;
+        }
+
+        let mut output = String::new();
+        for resource in &resources {
+            if !output.is_empty() {
+                output.push_str("---\n");
+            }
+            output.push_str(&serde_yaml::to_string(&resource.value)?);
+        }
+
+        Ok(output)
+    }
+
+    fn render_dir(&self, dir: &Path, stack: &mut Vec<PathBuf>) -> Result<Vec<Resource>> {
+        let dir = dir
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve {}", dir.display()))?;
+        if stack.contains(&dir) {
+            bail!("Cycle detected: {} includes itself", dir.display());
+        }
+        stack.push(dir.clone());
+
+        let (kustomization_path, kustomization) = read_kustomization(&dir)?;
+
+        let unsupported: Vec<&str> = kustomization
+            .keys()
+            .map(String::as_str)
+            .filter(|key| !SUPPORTED_FIELDS.contains(key))
+            .collect();
+        if !unsupported.is_empty() {
+            bail!(
+                "{} uses kustomize features not supported by the built-in renderer: {}",
+                kustomization_path.display(),
+                unsupported.join(", ")
+            );
+        }
+        if kustomization.get("kind").and_then(|k| k.as_str()) == Some("Component") {
+            bail!(
+                "{}: kustomize components are not supported by the built-in renderer",
+                kustomization_path.display()
+            );
+        }
+
+        let mut resources = Vec::new();
+        for reference in string_list(&kustomization, "resources")
+            .into_iter()
+            .chain(string_list(&kustomization, "bases"))
+        {
+            if reference.contains("://") || reference.starts_with("github.com/") || reference.starts_with("git@") {
+                bail!("Remote resource '{}' is not supported by the built-in renderer", reference);
+            }
+            let path = dir.join(&reference);
+            if path.is_dir() {
+                resources.extend(self.render_dir(&path, stack)?);
+            } else {
+                let content = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read resource {}", path.display()))?;
+                let origin = path.canonicalize()?;
+                resources.extend(parse_documents(&content, &path)?.into_iter().map(|value| Resource {
+                    original_name: value
+                        .pointer("/metadata/name")
+                        .and_then(|n| n.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    value,
+                    needs_hash: false,
+                    origin: Some(origin.clone()),
+                    transformations: Vec::new(),
+                }));
+            }
+        }
+
+        let options: GeneratorOptions = match kustomization.get("generatorOptions") {
+            Some(value) => serde_json::from_value(value.clone()).context("Invalid generatorOptions")?,
+            None => GeneratorOptions::default(),
+        };
+        for (field, kind) in [("configMapGenerator", "ConfigMap"), ("secretGenerator", "Secret")] {
+            let generators: Vec<Generator> = match kustomization.get(field) {
+                Some(value) => serde_json::from_value(value.clone()).with_context(|| format!("Invalid {}", field))?,
+                None => Vec::new(),
+            };
+            for generator in generators {
+                generate(&dir, kind, &generator, &options, &mut resources)?;
+            }
+        }
+
+        for reference in string_list(&kustomization, "patchesStrategicMerge") {
+            let content = if reference.contains('\n') {
+                reference
+            } else {
+                read_relative(&dir, &reference)?
+            };
+            let transformation = Transformation {
+                configured_in: kustomization_path.clone(),
+                transformer: "PatchStrategicMergeTransformer",
+            };
+            for patch in parse_documents(&content, &dir)? {
+                apply_strategic_merge(&mut resources, &patch, None, &transformation)?;
+            }
+        }
+        for (field, transformer) in [("patchesJson6902", "PatchJson6902Transformer"), ("patches", "PatchTransformer")] {
+            let patches: Vec<Patch> = match kustomization.get(field) {
+                Some(value) => serde_json::from_value(value.clone()).with_context(|| format!("Invalid {}", field))?,
+                None => Vec::new(),
+            };
+            let transformation = Transformation {
+                configured_in: kustomization_path.clone(),
+                transformer,
+            };
+            for patch in patches {
+                apply_patch(&dir, &mut resources, &patch, &transformation)?;
+            }
+        }
+
+        if let Some(namespace) = kustomization.get("namespace").and_then(|n| n.as_str()) {
+            for resource in resources.iter_mut() {
+                if CLUSTER_SCOPED_KINDS.contains(&resource.kind()) {
+                    continue;
+                }
+                if let Some(metadata) = metadata_mut(&mut resource.value) {
+                    metadata.insert("namespace".to_string(), Value::String(namespace.to_string()));
+                }
+            }
+        }
+
+        let prefix = kustomization.get("namePrefix").and_then(|p| p.as_str()).unwrap_or_default();
+        let suffix = kustomization.get("nameSuffix").and_then(|s| s.as_str()).unwrap_or_default();
+        if !prefix.is_empty() || !suffix.is_empty() {
+            let mut renames = HashMap::new();
+            for resource in resources.iter_mut() {
+                if resource.kind() == "Namespace" || resource.kind() == "CustomResourceDefinition" {
+                    continue;
+                }
+                let new_name = format!("{}{}{}", prefix, resource.name(), suffix);
+                renames.insert((resource.kind().to_string(), resource.name().to_string()), new_name.clone());
+                resource.set_name(new_name);
+            }
+            update_references(&mut resources, &renames, &self.workload_kinds);
+        }
+
+        if let Some(labels) = kustomization.get("commonLabels").and_then(|l| l.as_object()) {
+            for resource in resources.iter_mut() {
+                add_common_labels(&mut resource.value, labels, &self.workload_kinds);
+            }
+        }
+        if let Some(annotations) = kustomization.get("commonAnnotations").and_then(|a| a.as_object()) {
+            for resource in resources.iter_mut() {
+                merge_metadata(&mut resource.value, "annotations", annotations);
+                if let Some(template) = pod_template_mut(&mut resource.value, &self.workload_kinds) {
+                    merge_metadata(template, "annotations", annotations);
+                }
+            }
+        }
+
+        if let Some(images) = kustomization.get("images") {
+            let images: Vec<ImageOverride> = serde_json::from_value(images.clone()).context("Invalid images")?;
+            for resource in resources.iter_mut() {
+                apply_images(&mut resource.value, &images, &self.workload_kinds);
+            }
+        }
+
+        stack.pop();
+        Ok(resources)
+    }
+}
+
+fn read_kustomization(dir: &Path) -> Result<(PathBuf, Map<String, Value>)> {
+    let path = KUSTOMIZATION_FILES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+        .with_context(|| format!("No kustomization file in {}", dir.display()))?;
+    let kustomization = serde_yaml::from_str(&std::fs::read_to_string(&path)?)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    Ok((path, kustomization))
+}
+
+/// Adds the origin and transformation annotations kustomize writes when the
+/// root kustomization requests them through `buildMetadata`.
+fn annotate_build_metadata(resource: &mut Resource, root: &Path, build_metadata: &[String]) -> Result<()> {
+    let mut annotations = Map::new();
+
+    if build_metadata.iter().any(|m| m == "originAnnotations") {
+        if let Some(origin) = &resource.origin {
+            annotations.insert(
+                ORIGIN_ANNOTATION.to_string(),
+                Value::String(format!("path: {}\n", relative_path(origin, root).display())),
+            );
+        }
+    }
+
+    if build_metadata.iter().any(|m| m == "transformerAnnotations") && !resource.transformations.is_empty() {
+        let transformations: Vec<Value> = resource
+            .transformations
+            .iter()
+            .map(|t| {
+                serde_json::json!({
+                    "configuredIn": relative_path(&t.configured_in, root).display().to_string(),
+                    "configuredBy": { "apiVersion": "builtin", "kind": t.transformer },
+                })
+            })
+            .collect();
+        annotations.insert(
+            TRANSFORMATIONS_ANNOTATION.to_string(),
+            Value::String(serde_yaml::to_string(&transformations)?),
+        );
+    }
+
+    if !annotations.is_empty() {
+        merge_metadata(&mut resource.value, "annotations", &annotations);
+    }
+    Ok(())
+}
+
+/// Expresses `path` relative to `base`; both must be canonical.
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path_components: Vec<_> = path.components().collect();
+    let base_components: Vec<_> = base.components().collect();
+    let common = path_components
+        .iter()
+        .zip(&base_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..base_components.len() {
+        relative.push("..");
+    }
+    for component in &path_components[common..] {
+        relative.push(component);
+    }
+    relative
+}
+
+/// Parses the non-empty documents of a resource or patch file, each of which
+/// must be a mapping.
+fn parse_documents(content: &str, origin: &Path) -> Result<Vec<Value>> {
+    let mut documents = Vec::new();
+    for (index, document) in serde_yaml::Deserializer::from_str(content).enumerate() {
+        let value = Value::deserialize(document).with_context(|| format!("Failed to parse {}", origin.display()))?;
+        if value.is_null() {
+            continue;
+        }
+        if !value.is_object() {
+            bail!("Failed to parse {}: document {} is not a mapping", origin.display(), index + 1);
+        }
+        documents.push(value);
+    }
+    Ok(documents)
+}
+
+fn read_relative(dir: &Path, reference: &str) -> Result<String> {
+    let path = dir.join(reference);
+    std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+fn string_list(map: &Map<String, Value>, key: &str) -> Vec<String> {
+    match map.get(key) {
+        Some(Value::Array(items)) => items.iter().filter_map(|v| v.as_str()).map(str::to_string).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The `metadata` mapping of `value`, created if missing; `None` if `value` is
+/// not a mapping.
+fn metadata_mut(value: &mut Value) -> Option<&mut Map<String, Value>> {
+    let metadata = value
+        .as_object_mut()?
+        .entry("metadata")
+        .or_insert_with(|| Value::Object(Map::new()));
+    if !metadata.is_object() {
+        *metadata = Value::Object(Map::new());
+    }
+    metadata.as_object_mut()
+}
+
+/// Merges `entries` into `metadata.<key>` of `value`, if `value` is a mapping.
+fn merge_metadata(value: &mut Value, key: &str, entries: &Map<String, Value>) {
+    if let Some(metadata) = metadata_mut(value) {
+        merge_into(metadata, key, entries);
+    }
+}
+
+fn merge_into(object: &mut Map<String, Value>, key: &str, entries: &Map<String, Value>) {
+    let target = object.entry(key).or_insert_with(|| Value::Object(Map::new()));
+    if let Some(target) = target.as_object_mut() {
+        for (k, v) in entries {
+            target.insert(k.clone(), v.clone());
+        }
+    }
+}
+
+/// Path of the pod spec in `value`, from the workload kinds the checker uses or
+/// [`OTHER_POD_KINDS`]; `None` for kinds without a pod template.
+fn pod_spec_path(value: &Value, workload_kinds: &HashMap<String, String>) -> Option<Vec<String>> {
+    let kind = value.get("kind")?.as_str()?;
+    let api_version = value.get("apiVersion").and_then(|v| v.as_str()).unwrap_or_default();
+    crate::k8s::pod_spec_path(workload_kinds, api_version, kind).or_else(|| {
+        OTHER_POD_KINDS
+            .iter()
+            .find(|(other, _)| *other == kind)
+            .map(|(_, path)| crate::k8s::split_path(path))
+    })
+}
+
+fn object_at_mut<'a>(value: &'a mut Value, path: &[String]) -> Option<&'a mut Value> {
+    path.iter()
+        .try_fold(value, |value, key| value.get_mut(key.as_str()))
+        .filter(|value| value.is_object())
+}
+
+/// The pod template of a workload, whose `spec` is the pod spec; `None` when the
+/// pod spec is not held in a template.
+fn pod_template_mut<'a>(value: &'a mut Value, workload_kinds: &HashMap<String, String>) -> Option<&'a mut Value> {
+    let path = pod_spec_path(value, workload_kinds)?;
+    match path.split_last() {
+        Some((last, template)) if last == "spec" && !template.is_empty() => object_at_mut(value, template),
+        _ => None,
+    }
+}
+
+fn pod_spec_mut<'a>(value: &'a mut Value, workload_kinds: &HashMap<String, String>) -> Option<&'a mut Value> {
+    if value.get("kind").and_then(|k| k.as_str()) == Some("Pod") {
+        return value.get_mut("spec");
+    }
+    let path = pod_spec_path(value, workload_kinds)?;
+    object_at_mut(value, &path)
+}
+
+fn generate(
+    dir: &Path,
+    kind: &str,
+    generator: &Generator,
+    defaults: &GeneratorOptions,
+    resources: &mut Vec<Resource>,
+) -> Result<()> {
+    let mut data = BTreeMap::new();
+    for literal in &generator.literals {
+        let (key, value) = literal
+            .split_once('=')
+            .with_context(|| format!("Invalid literal '{}' in {} generator {}", literal, kind, generator.name))?;
+        data.insert(key.to_string(), unquote(value).to_string());
+    }
+    for env_file in generator.envs.iter().chain(generator.env.iter()) {
+        for (line_number, line) in read_relative(dir, env_file)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once('=').with_context(|| {
+                format!(
+                    "{}:{}: reading values from the process environment is not supported by the built-in renderer",
+                    env_file,
+                    line_number + 1
+                )
+            })?;
+            data.insert(key.trim().to_string(), unquote(value).to_string());
+        }
+    }
+    for file in &generator.files {
+        let (key, path) = match file.split_once('=') {
+            Some((key, path)) => (key.to_string(), path),
+            None => (
+                Path::new(file)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| file.clone()),
+                file.as_str(),
+            ),
+        };
+        data.insert(key, read_relative(dir, path)?);
+    }
+
+    let options = generator.options.as_ref();
+    let disable_hash = options.is_some_and(|o| o.disable_name_suffix_hash) || defaults.disable_name_suffix_hash;
+    let encoded: Map<String, Value> = data
+        .into_iter()
+        .map(|(k, v)| {
+            let v = if kind == "Secret" {
+                base64::engine::general_purpose::STANDARD.encode(v)
+            } else {
+                v
+            };
+            (k, Value::String(v))
+        })
+        .collect();
+
+    match generator.behavior.as_deref().unwrap_or("create") {
+        "create" => {
+            let mut value = serde_json::json!({
+                "apiVersion": "v1",
+                "kind": kind,
+                "metadata": { "name": generator.name },
+                "data": encoded,
+            });
+            if kind == "Secret" {
+                value["type"] = Value::String(generator.secret_type.clone().unwrap_or_else(|| "Opaque".to_string()));
+            }
+            if let Some(namespace) = &generator.namespace {
+                value["metadata"]["namespace"] = Value::String(namespace.clone());
+            }
+            for source in [Some(defaults), options].into_iter().flatten() {
+                let labels: Map<String, Value> =
+                    source.labels.iter().map(|(k, v)| (k.clone(), Value::String(v.clone()))).collect();
+                let annotations: Map<String, Value> =
+                    source.annotations.iter().map(|(k, v)| (k.clone(), Value::String(v.clone()))).collect();
+                if !labels.is_empty() {
+                    merge_metadata(&mut value, "labels", &labels);
+                }
+                if !annotations.is_empty() {
+                    merge_metadata(&mut value, "annotations", &annotations);
+                }
+            }
+            resources.push(Resource {
+                value,
+                original_name: generator.name.clone(),
+                needs_hash: !disable_hash,
+                origin: None,
+                transformations: Vec::new(),
+            });
+        }
+        behavior @ ("merge" | "replace") => {
+            let existing = resources
+                .iter_mut()
+                .find(|r| r.matches(kind, &generator.name))
+                .with_context(|| {
+                    format!(
+                        "{} generator {} has behavior '{}' but there is no existing {} with that name",
+                        kind, generator.name, behavior, kind
+                    )
+                })?;
+            let object = existing
+                .value
+                .as_object_mut()
+                .with_context(|| format!("{} {} is not a mapping", kind, generator.name))?;
+            if behavior == "replace" {
+                object.insert("data".to_string(), Value::Object(encoded));
+            } else {
+                merge_into(object, "data", &encoded);
+            }
+            existing.needs_hash = existing.needs_hash && !disable_hash;
+        }
+        other => bail!("Unknown generator behavior '{}' for {} {}", other, kind, generator.name),
+    }
+
+    Ok(())
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    if value.len() >= 2
+        && ((value.starts_with('"') && value.ends_with('"')) || (value.starts_with('\'') && value.ends_with('\'')))
+    {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    }
+}
+
+fn apply_patch(
+    dir: &Path,
+    resources: &mut Vec<Resource>,
+    patch: &Patch,
+    transformation: &Transformation,
+) -> Result<()> {
+    let content = match (&patch.path, &patch.patch) {
+        (Some(path), None) => read_relative(dir, path)?,
+        (None, Some(inline)) => inline.clone(),
+        _ => bail!("A patch must set exactly one of 'path' or 'patch'"),
+    };
+
+    if let Some(target) = &patch.target {
+        if target.label_selector.is_some() || target.annotation_selector.is_some() {
+            bail!("Patch targets with label or annotation selectors are not supported by the built-in renderer");
+        }
+    }
+
+    let parsed: Value = serde_yaml::from_str(&content).context("Failed to parse patch")?;
+    if let Value::Array(operations) = parsed {
+        let target = patch
+            .target
+            .as_ref()
+            .context("JSON6902 patches require a target")?;
+        let mut matched = false;
+        for resource in resources.iter_mut().filter(|r| target_matches(target, r)) {
+            matched = true;
+            for operation in &operations {
+                apply_json_patch_operation(&mut resource.value, operation)?;
+            }
+            resource.transformations.push(transformation.clone());
+        }
+        if !matched {
+            bail!("JSON6902 patch target {} did not match any resource", describe_target(target));
+        }
+        return Ok(());
+    }
+
+    for document in parse_documents(&content, dir)? {
+        apply_strategic_merge(resources, &document, patch.target.as_ref(), transformation)?;
+    }
+    Ok(())
+}
+
+fn target_matches(target: &PatchTarget, resource: &Resource) -> bool {
+    if let Some(kind) = &target.kind {
+        if resource.kind() != kind {
+            return false;
+        }
+    }
+    if let Some(name) = &target.name {
+        let anchored = Regex::new(&format!("^(?:{})$", name));
+        let name_matches = |candidate: &str| match &anchored {
+            Ok(regex) => regex.is_match(candidate),
+            Err(_) => candidate == name,
+        };
+        if !name_matches(resource.name()) && !name_matches(&resource.original_name) {
+            return false;
+        }
+    }
+    if let Some(namespace) = &target.namespace {
+        let actual = resource.value.pointer("/metadata/namespace").and_then(|n| n.as_str()).unwrap_or("default");
+        if actual != namespace {
+            return false;
+        }
+    }
+    if target.group.is_some() || target.version.is_some() {
+        let api_version = resource.value.get("apiVersion").and_then(|v| v.as_str()).unwrap_or_default();
+        let (group, version) = api_version.rsplit_once('/').unwrap_or(("", api_version));
+        if target.group.as_deref().is_some_and(|g| g != group) || target.version.as_deref().is_some_and(|v| v != version) {
+            return false;
+        }
+    }
+    true
+}
+
+fn describe_target(target: &PatchTarget) -> String {
+    format!(
+        "{}/{}",
+        target.kind.as_deref().unwrap_or("*"),
+        target.name.as_deref().unwrap_or("*")
+    )
+}
+
+fn apply_strategic_merge(
+    resources: &mut Vec<Resource>,
+    patch: &Value,
+    target: Option<&PatchTarget>,
+    transformation: &Transformation,
+) -> Result<()> {
+    let kind = patch.get("kind").and_then(|k| k.as_str()).unwrap_or_default();
+    let name = patch.pointer("/metadata/name").and_then(|n| n.as_str()).unwrap_or_default();
+
+    let indices: Vec<usize> = resources
+        .iter()
+        .enumerate()
+        .filter(|(_, r)| match target {
+            Some(target) => target_matches(target, r),
+            None => r.matches(kind, name),
+        })
+        .map(|(i, _)| i)
+        .collect();
+    if indices.is_empty() {
+        bail!("Strategic merge patch for {}/{} did not match any resource", kind, name);
+    }
+
+    if patch.get("$patch").and_then(|p| p.as_str()) == Some("delete") {
+        let mut index = 0;
+        resources.retain(|_| {
+            let keep = !indices.contains(&index);
+            index += 1;
+            keep
+        });
+        return Ok(());
+    }
+
+    for index in indices {
+        let resource = &mut resources[index];
+        let mut patch = patch.clone();
+        if target.is_some() {
+            // A targeted patch may name a placeholder resource; keep the target's identity.
+            if let Some(metadata) = patch.get_mut("metadata").and_then(|m| m.as_object_mut()) {
+                metadata.remove("name");
+            }
+        }
+        strategic_merge(&mut resource.value, &patch)?;
+        resource.transformations.push(transformation.clone());
+    }
+    Ok(())
+}
+
+fn merge_key(field: &str) -> Option<&'static str> {
+    match field {
+        "containers" | "initContainers" | "ephemeralContainers" | "env" | "volumes" | "imagePullSecrets" => Some("name"),
+        "ports" => Some("containerPort"),
+        "volumeMounts" => Some("mountPath"),
+        _ => None,
+    }
+}
+
+fn strategic_merge(target: &mut Value, patch: &Value) -> Result<()> {
+    let (Some(target), Some(patch)) = (target.as_object_mut(), patch.as_object()) else {
+        *target = patch.clone();
+        return Ok(());
+    };
+
+    for (key, patch_value) in patch {
+        if key.starts_with('$') {
+            if key == "$patch" {
+                continue;
+            }
+            bail!("Strategic merge directive '{}' is not supported by the built-in renderer", key);
+        }
+        if patch_value.is_null() {
+            target.remove(key);
+            continue;
+        }
+        match (target.get_mut(key), merge_key(key)) {
+            (Some(Value::Array(items)), Some(merge_key)) if patch_value.is_array() => {
+                merge_list(items, patch_value.as_array().expect("checked above"), merge_key)?;
+            }
+            (Some(existing @ Value::Object(_)), _) if patch_value.is_object() => {
+                strategic_merge(existing, patch_value)?;
+            }
+            _ => {
+                target.insert(key.clone(), strip_directives(patch_value));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn merge_list(items: &mut Vec<Value>, patch: &[Value], merge_key: &str) -> Result<()> {
+    for patch_item in patch {
+        let key = patch_item.get(merge_key);
+        let position = key.and_then(|key| items.iter().position(|item| item.get(merge_key) == Some(key)));
+        let directive = patch_item.get("$patch").and_then(|p| p.as_str());
+
+        match (position, directive) {
+            (Some(index), Some("delete")) => {
+                items.remove(index);
+            }
+            (None, Some("delete")) => {}
+            (_, Some(other)) if other != "merge" => {
+                bail!("Strategic merge directive '$patch: {}' in a list is not supported by the built-in renderer", other);
+            }
+            (Some(index), _) => strategic_merge(&mut items[index], patch_item)?,
+            (None, _) => items.push(strip_directives(patch_item)),
+        }
+    }
+    Ok(())
+}
+
+fn strip_directives(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(k, _)| !k.starts_with('$'))
+                .map(|(k, v)| (k.clone(), strip_directives(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(strip_directives).collect()),
+        other => other.clone(),
+    }
+}
+
+fn apply_json_patch_operation(document: &mut Value, operation: &Value) -> Result<()> {
+    let op = operation.get("op").and_then(|o| o.as_str()).context("JSON6902 operation without 'op'")?;
+    let path = operation.get("path").and_then(|p| p.as_str()).context("JSON6902 operation without 'path'")?;
+    let value = || operation.get("value").cloned().context("JSON6902 operation without 'value'");
+    let from = || operation.get("from").and_then(|f| f.as_str()).context("JSON6902 operation without 'from'");
+
+    match op {
+        "add" => json_pointer_add(document, path, value()?),
+        "remove" => json_pointer_remove(document, path).map(|_| ()),
+        "replace" => {
+            let slot = document
+                .pointer_mut(path)
+                .with_context(|| format!("JSON6902 replace: path {} does not exist", path))?;
+            *slot = value()?;
+            Ok(())
+        }
+        "move" => {
+            let moved = json_pointer_remove(document, from()?)?;
+            json_pointer_add(document, path, moved)
+        }
+        "copy" => {
+            let from = from()?;
+            let copied = document
+                .pointer(from)
+                .cloned()
+                .with_context(|| format!("JSON6902 copy: path {} does not exist", from))?;
+            json_pointer_add(document, path, copied)
+        }
+        "test" => {
+            if document.pointer(path) != Some(&value()?) {
+                bail!("JSON6902 test failed at {}", path);
+            }
+            Ok(())
+        }
+        other => bail!("Unknown JSON6902 operation '{}'", other),
+    }
+}
+
+fn split_pointer(path: &str) -> Result<(&str, String)> {
+    let (parent, last) = path
+        .rsplit_once('/')
+        .with_context(|| format!("Invalid JSON pointer '{}'", path))?;
+    Ok((parent, last.replace("~1", "/").replace("~0", "~")))
+}
+
+fn json_pointer_add(document: &mut Value, path: &str, value: Value) -> Result<()> {
+    let (parent_path, last) = split_pointer(path)?;
+    let parent = document
+        .pointer_mut(parent_path)
+        .with_context(|| format!("JSON6902 add: parent of {} does not exist", path))?;
+    match parent {
+        Value::Object(map) => {
+            map.insert(last, value);
+        }
+        Value::Array(items) => {
+            if last == "-" {
+                items.push(value);
+            } else {
+                let index: usize = last.parse().with_context(|| format!("Invalid array index in {}", path))?;
+                if index > items.len() {
+                    bail!("JSON6902 add: index out of range in {}", path);
+                }
+                items.insert(index, value);
+            }
+        }
+        _ => bail!("JSON6902 add: parent of {} is not a container", path),
+    }
+    Ok(())
+}
+
+fn json_pointer_remove(document: &mut Value, path: &str) -> Result<Value> {
+    let (parent_path, last) = split_pointer(path)?;
+    let parent = document
+        .pointer_mut(parent_path)
+        .with_context(|| format!("JSON6902 remove: parent of {} does not exist", path))?;
+    let removed = match parent {
+        Value::Object(map) => map.remove(&last),
+        Value::Array(items) => last
+            .parse::<usize>()
+            .ok()
+            .filter(|index| *index < items.len())
+            .map(|index| items.remove(index)),
+        _ => None,
+    };
+    removed.with_context(|| format!("JSON6902 remove: path {} does not exist", path))
+}
+
+fn add_common_labels(value: &mut Value, labels: &Map<String, Value>, workload_kinds: &HashMap<String, String>) {
+    merge_metadata(value, "labels", labels);
+
+    let kind = value.get("kind").and_then(|k| k.as_str()).unwrap_or_default().to_string();
+    if matches!(kind.as_str(), "Deployment" | "StatefulSet" | "DaemonSet" | "ReplicaSet") {
+        if let Some(spec) = value.get_mut("spec").and_then(|s| s.as_object_mut()) {
+            let selector = spec.entry("selector").or_insert_with(|| Value::Object(Map::new()));
+            if let Some(selector) = selector.as_object_mut() {
+                merge_into(selector, "matchLabels", labels);
+            }
+        }
+    } else if pod_template_mut(value, workload_kinds).is_some() {
+        // Other workloads, such as Rollouts, keep a selector only if they declare one.
+        if let Some(selector) = value.pointer_mut("/spec/selector").and_then(|s| s.as_object_mut()) {
+            merge_into(selector, "matchLabels", labels);
+        }
+    }
+    if kind == "Service" {
+        if let Some(spec) = value.get_mut("spec").and_then(|s| s.as_object_mut()) {
+            merge_into(spec, "selector", labels);
+        }
+    }
+    if let Some(template) = pod_template_mut(value, workload_kinds) {
+        merge_metadata(template, "labels", labels);
+    }
+}
+
+fn apply_images(value: &mut Value, images: &[ImageOverride], workload_kinds: &HashMap<String, String>) {
+    let Some(spec) = pod_spec_mut(value, workload_kinds) else {
+        return;
+    };
+    for field in ["containers", "initContainers"] {
+        let Some(containers) = spec.get_mut(field).and_then(|c| c.as_array_mut()) else {
+            continue;
+        };
+        for container in containers {
+            let Some(image) = container.get("image").and_then(|i| i.as_str()) else {
+                continue;
+            };
+            let (name, tag) = split_image(image);
+            let Some(image_override) = images.iter().find(|o| o.name == name) else {
+                continue;
+            };
+            let new_name = image_override.new_name.as_deref().unwrap_or(name);
+            let new_image = if let Some(digest) = &image_override.digest {
+                format!("{}@{}", new_name, digest)
+            } else if let Some(tag) = &image_override.new_tag {
+                format!("{}:{}", new_name, tag)
+            } else {
+                format!("{}{}", new_name, tag)
+            };
+            container["image"] = Value::String(new_image);
+        }
+    }
+}
+
+/// Splits an image reference into its name and the `:tag`/`@digest` suffix.
+fn split_image(image: &str) -> (&str, &str) {
+    if let Some(index) = image.find('@') {
+        return image.split_at(index);
+    }
+    let last_segment = image.rfind('/').map_or(0, |i| i + 1);
+    match image[last_segment..].find(':') {
+        Some(index) => image.split_at(last_segment + index),
+        None => (image, ""),
+    }
+}
+
+/// Rewrites ConfigMap and Secret references in pod specs after resources were renamed.
+fn update_references(
+    resources: &mut [Resource],
+    renames: &HashMap<(String, String), String>,
+    workload_kinds: &HashMap<String, String>,
+) {
+    let rename = |kind: &str, name: &mut Value| {
+        if let Some(new_name) = name.as_str().and_then(|n| renames.get(&(kind.to_string(), n.to_string()))) {
+            *name = Value::String(new_name.clone());
+        }
+    };
+
+    for resource in resources.iter_mut() {
+        let Some(spec) = pod_spec_mut(&mut resource.value, workload_kinds) else {
+            continue;
+        };
+
+        for field in ["containers", "initContainers"] {
+            let Some(containers) = spec.get_mut(field).and_then(|c| c.as_array_mut()) else {
+                continue;
+            };
+            for container in containers {
+                if let Some(env) = container.get_mut("env").and_then(|e| e.as_array_mut()) {
+                    for var in env {
+                        if let Some(name) = var.pointer_mut("/valueFrom/configMapKeyRef/name") {
+                            rename("ConfigMap", name);
+                        }
+                        if let Some(name) = var.pointer_mut("/valueFrom/secretKeyRef/name") {
+                            rename("Secret", name);
+                        }
+                    }
+                }
+                if let Some(env_from) = container.get_mut("envFrom").and_then(|e| e.as_array_mut()) {
+                    for source in env_from {
+                        if let Some(name) = source.pointer_mut("/configMapRef/name") {
+                            rename("ConfigMap", name);
+                        }
+                        if let Some(name) = source.pointer_mut("/secretRef/name") {
+                            rename("Secret", name);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(volumes) = spec.get_mut("volumes").and_then(|v| v.as_array_mut()) {
+            for volume in volumes {
+                if let Some(name) = volume.pointer_mut("/configMap/name") {
+                    rename("ConfigMap", name);
+                }
+                if let Some(name) = volume.pointer_mut("/secret/secretName") {
+                    rename("Secret", name);
+                }
+            }
+        }
+    }
+}
+
+/// Appends kustomize's content hash suffix to generated ConfigMaps and Secrets
+/// and updates the references to them.
+fn apply_name_hashes(resources: &mut [Resource], workload_kinds: &HashMap<String, String>) -> Result<()> {
+    let mut renames = HashMap::new();
+    let mut seen = HashSet::new();
+    for resource in resources.iter_mut().filter(|r| r.needs_hash) {
+        let hash = content_hash(&resource.value)?;
+        let new_name = format!("{}-{}", resource.name(), hash);
+        if seen.insert((resource.kind().to_string(), resource.name().to_string())) {
+            renames.insert((resource.kind().to_string(), resource.name().to_string()), new_name.clone());
+        }
+        resource.set_name(new_name);
+    }
+    update_references(resources, &renames, workload_kinds);
+    Ok(())
+}
+
+/// Mirrors kustomize's ConfigMap/Secret hash: SHA-256 over the JSON encoding of
+/// kind, name, data (and type for Secrets), first ten hex digits with vowel-like
+/// characters swapped out so the suffix never spells words.
+fn content_hash(value: &Value) -> Result<String> {
+    let kind = value.get("kind").and_then(|k| k.as_str()).unwrap_or_default();
+    let mut encoded = BTreeMap::new();
+    encoded.insert("kind", value.get("kind").cloned().unwrap_or(Value::Null));
+    encoded.insert("name", value.pointer("/metadata/name").cloned().unwrap_or(Value::Null));
+    encoded.insert("data", value.get("data").cloned().unwrap_or_else(|| Value::Object(Map::new())));
+    if kind == "Secret" {
+        encoded.insert("type", value.get("type").cloned().unwrap_or(Value::Null));
+    }
+
+    // Go's encoding/json escapes HTML-sensitive characters; match it byte for byte.
+    let json = serde_json::to_string(&encoded)?
+        .replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026")
+        .replace('\u{2028}', "\\u2028")
+        .replace('\u{2029}', "\\u2029");
+    let digest = format!("{:x}", Sha256::digest(json.as_bytes()));
+
+    Ok(digest[..10]
+        .chars()
+        .map(|c| match c {
+            '0' => 'g',
+            '1' => 'h',
+            '3' => 'k',
+            'a' => 'm',
+            'e' => 't',
+            other => other,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Writes `files` into a temporary directory, removed when it is dropped.
+    fn fixture(files: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn render(dir: &Path) -> Vec<Value> {
+        let output = BuiltinRenderer::new().render(dir).unwrap();
+        parse_documents(&output, dir).unwrap()
+    }
+
+    fn find<'a>(resources: &'a [Value], kind: &str) -> &'a Value {
+        resources.iter().find(|r| r["kind"] == kind).unwrap()
+    }
+
+    const DEPLOYMENT: &str = "\
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: web
+spec:
+  template:
+    spec:
+      containers:
+        - name: web
+          image: web:1
+          env:
+            - name: LOG_LEVEL
+              value: info
+            - name: PORT
+              value: \"8080\"
+          envFrom:
+            - configMapRef:
+                name: settings
+";
+
+    #[test]
+    fn affixes_rename_resources_and_references() {
+        let dir = fixture(&[
+            ("base/kustomization.yaml", "resources: [deployment.yaml]\nconfigMapGenerator:\n  - name: settings\n    literals: [A=1]\n    options:\n      disableNameSuffixHash: true\n"),
+            ("base/deployment.yaml", DEPLOYMENT),
+            ("overlay/kustomization.yaml", "resources: [../base]\nnamePrefix: prod-\nnameSuffix: -v2\nnamespace: shop\n"),
+        ]);
+        let resources = render(&dir.path().join("overlay"));
+
+        let deployment = find(&resources, "Deployment");
+        assert_eq!(deployment["metadata"]["name"], "prod-web-v2");
+        assert_eq!(deployment["metadata"]["namespace"], "shop");
+        assert_eq!(
+            deployment["spec"]["template"]["spec"]["containers"][0]["envFrom"][0]["configMapRef"]["name"],
+            "prod-settings-v2"
+        );
+        assert_eq!(find(&resources, "ConfigMap")["metadata"]["name"], "prod-settings-v2");
+    }
+
+    #[test]
+    fn generated_names_get_content_hash() {
+        let dir = fixture(&[
+            ("kustomization.yaml", "resources: [deployment.yaml]\nconfigMapGenerator:\n  - name: settings\n    literals: [A=1]\n"),
+            ("deployment.yaml", DEPLOYMENT),
+        ]);
+        let resources = render(dir.path());
+
+        let config_map = find(&resources, "ConfigMap");
+        let name = config_map["metadata"]["name"].as_str().unwrap();
+        assert_eq!(name, format!("settings-{}", content_hash(&serde_json::json!({
+            "kind": "ConfigMap",
+            "metadata": { "name": "settings" },
+            "data": { "A": "1" },
+        })).unwrap()));
+        assert_eq!(
+            find(&resources, "Deployment")["spec"]["template"]["spec"]["containers"][0]["envFrom"][0]["configMapRef"]["name"],
+            name
+        );
+    }
+
+    #[test]
+    fn rollout_pod_template_is_transformed() {
+        let rollout = DEPLOYMENT
+            .replace("apps/v1", "argoproj.io/v1alpha1")
+            .replace("kind: Deployment", "kind: Rollout")
+            .replace("spec:\n  template:", "spec:\n  selector:\n    matchLabels:\n      app: web\n  template:");
+        let dir = fixture(&[
+            (
+                "kustomization.yaml",
+                "resources: [rollout.yaml]\nconfigMapGenerator:\n  - name: settings\n    literals: [A=1]\ncommonLabels:\n  team: shop\nimages:\n  - name: web\n    newTag: \"2\"\n",
+            ),
+            ("rollout.yaml", &rollout),
+        ]);
+        let resources = render(dir.path());
+
+        let rollout = find(&resources, "Rollout");
+        let config_map = find(&resources, "ConfigMap")["metadata"]["name"].as_str().unwrap();
+        assert_eq!(rollout["spec"]["selector"]["matchLabels"]["team"], "shop");
+        assert_eq!(rollout["spec"]["template"]["metadata"]["labels"]["team"], "shop");
+        let container = &rollout["spec"]["template"]["spec"]["containers"][0];
+        assert_eq!(container["image"], "web:2");
+        assert_eq!(container["envFrom"][0]["configMapRef"]["name"], config_map);
+    }
+
+    #[test]
+    fn configured_workload_kinds_are_transformed() {
+        let worker = DEPLOYMENT
+            .replace("apps/v1", "example.com/v1")
+            .replace("kind: Deployment", "kind: Worker")
+            .replace("  template:\n    spec:", "  podTemplate:\n    spec:");
+        let dir = fixture(&[
+            ("kustomization.yaml", "resources: [worker.yaml]\ncommonLabels:\n  team: shop\nimages:\n  - name: web\n    newTag: \"2\"\n"),
+            ("worker.yaml", &worker),
+        ]);
+        let workload_kinds = HashMap::from([("example.com/v1/Worker".to_string(), "spec.podTemplate.spec".to_string())]);
+        let output = BuiltinRenderer::new().with_workload_kinds(workload_kinds).render(dir.path()).unwrap();
+        let resources = parse_documents(&output, dir.path()).unwrap();
+
+        let worker = find(&resources, "Worker");
+        assert_eq!(worker["spec"]["podTemplate"]["metadata"]["labels"]["team"], "shop");
+        assert_eq!(worker["spec"]["podTemplate"]["spec"]["containers"][0]["image"], "web:2");
+        assert!(worker["spec"].get("selector").is_none());
+    }
+
+    #[test]
+    fn content_hash_matches_kustomize() {
+        let config_map = |data: Value| serde_json::json!({ "kind": "ConfigMap", "metadata": { "name": "" }, "data": data });
+        assert_eq!(content_hash(&config_map(serde_json::json!({}))).unwrap(), "42745tchd9");
+        assert_eq!(content_hash(&config_map(serde_json::json!({ "one": "" }))).unwrap(), "9g67k2htb6");
+        assert_eq!(
+            content_hash(&config_map(serde_json::json!({ "two": "2", "one": "", "three": "3" }))).unwrap(),
+            "f5h7t85m9b"
+        );
+    }
+
+    #[test]
+    fn strategic_merge_patch_merges_env_by_name() {
+        let dir = fixture(&[
+            ("kustomization.yaml", "resources: [deployment.yaml]\npatches:\n  - path: patch.yaml\n"),
+            ("deployment.yaml", DEPLOYMENT),
+            (
+                "patch.yaml",
+                "apiVersion: apps/v1\nkind: Deployment\nmetadata:\n  name: web\nspec:\n  template:\n    spec:\n      containers:\n        - name: web\n          env:\n            - name: LOG_LEVEL\n              value: debug\n            - name: PORT\n              $patch: delete\n            - name: EXTRA\n              value: x\n",
+            ),
+        ]);
+        let resources = render(dir.path());
+
+        let env = &find(&resources, "Deployment")["spec"]["template"]["spec"]["containers"][0]["env"];
+        assert_eq!(
+            env,
+            &serde_json::json!([{ "name": "LOG_LEVEL", "value": "debug" }, { "name": "EXTRA", "value": "x" }])
+        );
+    }
+
+    #[test]
+    fn json6902_patch_applies_to_target() {
+        let dir = fixture(&[
+            (
+                "kustomization.yaml",
+                "resources: [deployment.yaml]\npatches:\n  - target: {kind: Deployment, name: web}\n    patch: |-\n      - op: replace\n        path: /spec/template/spec/containers/0/env/0/value\n        value: warn\n",
+            ),
+            ("deployment.yaml", DEPLOYMENT),
+        ]);
+        let resources = render(dir.path());
+
+        assert_eq!(
+            find(&resources, "Deployment")["spec"]["template"]["spec"]["containers"][0]["env"][0]["value"],
+            "warn"
+        );
+    }
+
+    #[test]
+    fn json6902_copy_reports_missing_source() {
+        let dir = fixture(&[
+            (
+                "kustomization.yaml",
+                "resources: [deployment.yaml]\npatches:\n  - target: {kind: Deployment, name: web}\n    patch: |-\n      - op: copy\n        from: /spec/replicas\n        path: /spec/minReadySeconds\n",
+            ),
+            ("deployment.yaml", DEPLOYMENT),
+        ]);
+        let error = BuiltinRenderer::new().render(dir.path()).unwrap_err();
+        assert!(format!("{:#}", error).contains("JSON6902 copy: path /spec/replicas does not exist"), "{:#}", error);
+    }
+
+    #[test]
+    fn patch_without_match_is_an_error() {
+        let dir = fixture(&[
+            ("kustomization.yaml", "resources: [deployment.yaml]\npatches:\n  - path: patch.yaml\n"),
+            ("deployment.yaml", DEPLOYMENT),
+            ("patch.yaml", "apiVersion: apps/v1\nkind: Deployment\nmetadata:\n  name: api\n"),
+        ]);
+        let error = BuiltinRenderer::new().render(dir.path()).unwrap_err();
+        assert!(error.to_string().contains("did not match any resource"), "{}", error);
+    }
+
+    #[test]
+    fn documents_that_are_not_mappings_are_rejected() {
+        let error = parse_documents("kind: ConfigMap\n---\njust a string\n", Path::new("r.yaml")).unwrap_err();
+        assert_eq!(error.to_string(), "Failed to parse r.yaml: document 2 is not a mapping");
+    }
+}
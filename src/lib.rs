@@ -0,0 +1,55 @@
+//! Checks the environment variables of workloads in Kustomize-built Kubernetes
+//! manifests against a configuration of required and optional variables.
+//!
+//! The `kustomize-envcheck` binary is a thin wrapper around this crate; the
+//! usual flow is to load a [`Config`], render an overlay with a
+//! [`KustomizeBuilder`](kustomize::KustomizeBuilder), parse the output with
+//! [`K8sParser`] and run an [`EnvChecker`] over the workloads:
+//!
+//! ```no_run
+//! use kustomize_envcheck::kustomize::KustomizeBuilder;
+//! use kustomize_envcheck::{CheckStatus, Config, EnvChecker, K8sParser};
+//! use std::path::Path;
+//!
+//! # async fn run() -> kustomize_envcheck::Result<()> {
+//! let overlay = Path::new("overlays/production");
+//! let config = Config::from_path(Path::new("envcheck.yaml"))?;
+//! let environment = config.resolve_environment(None, overlay)?;
+//!
+//! let rendered = KustomizeBuilder::new().build(overlay).await?;
+//! let resources = K8sParser::new().parse_yaml(&rendered)?;
+//!
+//! let checker = EnvChecker::new(config);
+//! for result in checker.check_resources(&resources, &environment) {
+//!     if matches!(result.status, CheckStatus::Failed) {
+//!         println!("{} is missing {:?}", result.app_name, result.missing_required);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod baseline;
+pub mod cache;
+pub mod checker;
+pub mod config;
+pub mod diagnostics;
+mod error;
+mod expansion;
+mod expr;
+pub mod init;
+pub mod k8s;
+pub mod kustomize;
+#[cfg(feature = "builtin-kustomize")]
+mod kustomize_builtin;
+pub mod output;
+pub mod provenance;
+pub mod rules;
+#[cfg(feature = "wasm-plugins")]
+pub mod wasm_plugin;
+
+pub use checker::{CheckResult, CheckStatus, EnvChecker};
+pub use config::Config;
+pub use error::{Error, Result};
+pub use k8s::{K8sParser, K8sResource};
+pub use output::{OutputFormat, OutputFormatter};
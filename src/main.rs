@@ -1,80 +1,309 @@
 mod cli;
-mod config;
-mod kustomize;
-mod k8s;
-mod checker;
-mod output;
 
 use anyhow::{Context, Result};
 use clap::Parser;
-use cli::Cli;
-use config::Config;
-use checker::EnvChecker;
-use kustomize::KustomizeBuilder;
-use k8s::K8sParser;
-use output::OutputFormatter;
-use std::path::Path;
+use cli::{CacheCommand, CheckArgs, Cli, Command, ConfigCommand, FailOn, InitArgs, RendererArg};
+use kustomize_envcheck::baseline::Baseline;
+use kustomize_envcheck::cache::BuildCache;
+use kustomize_envcheck::checker::CheckStatus;
+use kustomize_envcheck::config::{Config, Severity};
+use kustomize_envcheck::diagnostics::ConfigErrors;
+use kustomize_envcheck::init::StarterConfig;
+use kustomize_envcheck::kustomize::{KustomizeBuilder, KustomizeError, NameAffixes};
+use kustomize_envcheck::provenance::ProvenanceResolver;
+use kustomize_envcheck::rules::RuleRegistry;
+use kustomize_envcheck::{EnvChecker, Error, K8sParser, K8sResource, OutputFormatter};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::time::Duration;
+
+/// Findings at or above the `--fail-on` severity.
+const EXIT_FINDINGS: i32 = 1;
+/// Invalid configuration, arguments or other input.
+const EXIT_CONFIG_ERROR: i32 = 2;
+/// An overlay could not be rendered, or its output could not be parsed.
+const EXIT_BUILD_ERROR: i32 = 3;
 
 #[tokio::main]
 async fn main() {
     if let Err(e) = run().await {
         eprintln!("Error: {:#}", e);
-        process::exit(1);
+        let build_failed = e.is::<KustomizeError>()
+            || matches!(
+                e.downcast_ref::<Error>(),
+                Some(Error::Kustomize(_) | Error::KustomizeNotInstalled { .. } | Error::Manifest(_))
+            );
+        process::exit(if build_failed { EXIT_BUILD_ERROR } else { EXIT_CONFIG_ERROR });
     }
 }
 
 async fn run() -> Result<()> {
     let cli = Cli::parse();
 
+    match cli.command {
+        Some(Command::Cache(CacheCommand::Prune { cache_dir, older_than })) => {
+            let dir = cache_dir.map(PathBuf::from).unwrap_or_else(BuildCache::default_dir);
+            let max_age = older_than.map(|days| Duration::from_secs(days * 24 * 60 * 60));
+            let removed = BuildCache::prune(&dir, max_age)
+                .with_context(|| format!("Failed to prune cache in {}", dir.display()))?;
+            println!("Removed {} cached build(s) from {}", removed, dir.display());
+            Ok(())
+        }
+        Some(Command::Config(ConfigCommand::Lint { config })) => {
+            match Config::lint_path(Path::new(&config)) {
+                Ok(_) => {
+                    println!("{}: no problems found", config);
+                    Ok(())
+                }
+                Err(diagnostics) => {
+                    for diagnostic in &diagnostics {
+                        println!("{}", diagnostic);
+                    }
+                    process::exit(EXIT_CONFIG_ERROR);
+                }
+            }
+        }
+        Some(Command::Config(ConfigCommand::Schema)) => {
+            println!("{}", serde_json::to_string_pretty(&schemars::schema_for!(Config))?);
+            Ok(())
+        }
+        Some(Command::Init(args)) => run_init(args).await,
+        Some(Command::Rules { config }) => {
+            let config = match config {
+                Some(path) => Config::from_path(Path::new(&path))
+                    .with_context(|| format!("Failed to load config from {}", path))?,
+                None => Config::default(),
+            };
+            let rules = load_rules(&config)?;
+            let width = rules.iter().map(|rule| rule.id().len()).max().unwrap_or(0);
+            for rule in rules.iter() {
+                println!("{:width$}  {}", rule.id(), rule.description(), width = width);
+            }
+            Ok(())
+        }
+        None => run_check(cli.check).await,
+    }
+}
+
+async fn run_check(cli: CheckArgs) -> Result<()> {
     if cli.verbose {
         eprintln!("Verbose mode enabled");
     }
 
-    KustomizeBuilder::check_kustomize_installed()
-        .context("Kustomize check failed")?;
-
-    let config_path = Path::new(&cli.config);
-    let config = Config::from_file(config_path)
-        .with_context(|| format!("Failed to load config from {}", cli.config))?;
+    // Only optional so that subcommands can be parsed; clap enforces it otherwise.
+    let config_file = cli.config.as_deref().context("--config is required")?;
+    let config = Config::from_path(Path::new(config_file))
+        .with_context(|| format!("Failed to load config from {}", config_file))?;
+    let rules = load_rules(&config)?;
+    let baseline = match &cli.baseline {
+        Some(path) => Baseline::load(Path::new(path))?,
+        None => Baseline::default(),
+    };
 
-    let kustomize_dir = Path::new(&cli.kustomize_dir);
-    if !kustomize_dir.exists() {
-        anyhow::bail!("Kustomize directory does not exist: {}", cli.kustomize_dir);
+    let kustomize_dirs: Vec<PathBuf> = cli.kustomize_dir.iter().map(PathBuf::from).collect();
+    let mut environments = Vec::new();
+    for dir in &kustomize_dirs {
+        if !dir.exists() {
+            anyhow::bail!("Kustomize directory does not exist: {}", dir.display());
+        }
+        let environment = config.resolve_environment(cli.environment.as_deref(), dir)?;
+        if cli.verbose {
+            eprintln!("Checking {} against environment '{}'", dir.display(), environment);
+        }
+        environments.push(environment);
     }
 
     if cli.verbose {
-        eprintln!("Building Kustomize manifests from: {}", cli.kustomize_dir);
+        eprintln!("Building Kustomize manifests from: {}", cli.kustomize_dir.join(", "));
     }
 
-    let kustomize_builder = KustomizeBuilder::new();
-    let yaml_content = kustomize_builder.build(kustomize_dir).await
-        .with_context(|| format!("Failed to build Kustomize from {}", cli.kustomize_dir))?;
+    let mut kustomize_builder = KustomizeBuilder::new()
+        .with_timeout(Duration::from_secs(cli.timeout))
+        .with_jobs(cli.jobs)
+        .with_renderer(cli.renderer.into())
+        .with_workload_kinds(config.workload_kinds.clone());
+    if cli.renderer == RendererArg::Kustomize {
+        let kustomize_version = KustomizeBuilder::check_kustomize_installed().await
+            .context("Kustomize check failed")?;
+        if !cli.no_cache {
+            let cache_dir = cli.cache_dir.as_deref().map(PathBuf::from).unwrap_or_else(BuildCache::default_dir);
+            kustomize_builder = kustomize_builder.with_cache(BuildCache::new(cache_dir, kustomize_version));
+        }
+    }
+    let yaml_contents = build_overlays(&kustomize_builder, &kustomize_dirs).await?;
 
     if cli.verbose {
         eprintln!("Parsing Kubernetes resources...");
     }
 
-    let parser = K8sParser::new();
-    let resources = parser.parse_yaml(&yaml_content)
-        .context("Failed to parse Kubernetes YAML")?;
+    // Overlays checked against the same environment are pooled, so coverage
+    // findings consider every workload rendered for that environment.
+    let parser = K8sParser::new().with_workload_kinds(config.workload_kinds.clone());
+    let mut batches: Vec<(String, Vec<K8sResource>)> = Vec::new();
+    for ((dir, yaml_content), environment) in kustomize_dirs.iter().zip(&yaml_contents).zip(environments) {
+        let mut parsed = parse_overlay(&parser, dir, yaml_content)?;
+        ProvenanceResolver::new(dir).resolve(&mut parsed);
+        match batches.iter_mut().find(|(env, _)| *env == environment) {
+            Some((_, resources)) => resources.extend(parsed),
+            None => batches.push((environment, parsed)),
+        }
+    }
 
     if cli.verbose {
-        eprintln!("Found {} resources", resources.len());
+        let total: usize = batches.iter().map(|(_, resources)| resources.len()).sum();
+        eprintln!("Found {} resources", total);
     }
 
-    let checker = EnvChecker::new(config);
-    let results = checker.check_resources(&resources, cli.environment.as_deref());
+    let mut checker = EnvChecker::new(config)
+        .with_rules(rules)
+        .with_suppressions_allowed(!cli.no_suppressions)
+        .with_baseline(baseline);
+    for id in &cli.disable_rule {
+        if !checker.rules_mut().disable(id) {
+            let ids: Vec<&str> = checker.rules().iter().map(|rule| rule.id()).collect();
+            anyhow::bail!("Unknown rule '{}' in --disable-rule; available: {}", id, ids.join(", "));
+        }
+    }
+    let mut results = Vec::new();
+    let mut coverage = Vec::new();
+    for (environment, resources) in &batches {
+        results.extend(checker.check_resources(resources, environment));
+        coverage.extend(checker.check_coverage(resources, environment));
+    }
 
-    let formatter = OutputFormatter::new(cli.show_extra_vars, cli.verbose);
-    let output = formatter.format(&results, &cli.output)?;
+    if let Some(path) = &cli.write_baseline {
+        let written = Baseline::from_results(&results);
+        written.save(Path::new(path))?;
+        eprintln!("Wrote {} finding(s) to baseline {}", written.len(), path);
+    }
+
+    let formatter = OutputFormatter::new(cli.show_extra_vars, cli.verbose)
+        .with_fixed(checker.fixed(&results));
+    let output = formatter.format(&results, &coverage, &cli.output.into())?;
 
     println!("{}", output);
 
-    let has_failures = results.iter().any(|r| matches!(r.status, checker::CheckStatus::Failed));
-    if has_failures {
-        process::exit(1);
+    let threshold = match cli.fail_on {
+        FailOn::Warning => Severity::Warning,
+        FailOn::Error => Severity::Error,
+        FailOn::Never => return Ok(()),
+    };
+    // Findings just written to a baseline are accepted.
+    let checked = if cli.write_baseline.is_none() { &results[..] } else { &[] };
+    let worst = checked
+        .iter()
+        .map(|r| match r.status {
+            CheckStatus::Failed => Severity::Error,
+            CheckStatus::Warning => Severity::Warning,
+            _ => Severity::Info,
+        })
+        .chain(coverage.iter().map(|f| f.severity))
+        .max();
+    if worst.is_some_and(|worst| worst >= threshold) {
+        process::exit(EXIT_FINDINGS);
+    }
+
+    Ok(())
+}
+
+async fn run_init(args: InitArgs) -> Result<()> {
+    let output = Path::new(&args.config);
+    if matches!(output.extension().and_then(|e| e.to_str()), Some("json" | "toml")) {
+        anyhow::bail!("init writes YAML; use a .yaml or .yml path for --config");
+    }
+    if output.exists() {
+        anyhow::bail!("{} already exists; remove it or choose another path with --config", output.display());
+    }
+
+    let kustomize_dirs: Vec<PathBuf> = args.kustomize_dir.iter().map(PathBuf::from).collect();
+    let mut environments: Vec<String> = Vec::new();
+    for dir in &kustomize_dirs {
+        let environment = dir
+            .canonicalize()
+            .with_context(|| format!("Kustomize directory does not exist: {}", dir.display()))?
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(str::to_string)
+            .with_context(|| format!("Cannot name an environment after {}", dir.display()))?;
+        if environments.contains(&environment) {
+            anyhow::bail!(
+                "Two overlays would both define environment '{}'; each overlay directory needs a distinct name",
+                environment
+            );
+        }
+        environments.push(environment);
+    }
+
+    let kustomize_builder = KustomizeBuilder::new()
+        .with_timeout(Duration::from_secs(args.timeout))
+        .with_jobs(args.jobs)
+        .with_renderer(args.renderer.into());
+    if args.renderer == RendererArg::Kustomize {
+        KustomizeBuilder::check_kustomize_installed().await
+            .context("Kustomize check failed")?;
+    }
+    let yaml_contents = build_overlays(&kustomize_builder, &kustomize_dirs).await?;
+
+    let parser = K8sParser::new();
+    let mut starter = StarterConfig::new();
+    for ((dir, yaml_content), environment) in kustomize_dirs.iter().zip(&yaml_contents).zip(&environments) {
+        let parsed = parse_overlay(&parser, dir, yaml_content)?;
+        starter.add_overlay(&dir.display().to_string(), environment, &parsed);
     }
 
+    let content = starter.render();
+    Config::lint(output, &content)
+        .map_err(ConfigErrors)
+        .context("Generated configuration is invalid")?;
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(output)
+        .with_context(|| format!("Failed to create {}", output.display()))?;
+    std::io::Write::write_all(&mut file, content.as_bytes())
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+
+    println!(
+        "Wrote {} with {} environment(s) and {} application(s)",
+        output.display(),
+        environments.len(),
+        starter.application_count()
+    );
     Ok(())
 }
+
+/// The built-in checks plus any plugins the configuration lists.
+#[cfg(feature = "wasm-plugins")]
+fn load_rules(config: &Config) -> Result<RuleRegistry> {
+    let mut rules = RuleRegistry::builtin(config);
+    rules.register_plugins(config)?;
+    Ok(rules)
+}
+
+#[cfg(not(feature = "wasm-plugins"))]
+fn load_rules(config: &Config) -> Result<RuleRegistry> {
+    if !config.plugins.is_empty() {
+        anyhow::bail!("The configuration lists plugins, but this build lacks the wasm-plugins feature");
+    }
+    Ok(RuleRegistry::builtin(config))
+}
+
+async fn build_overlays(builder: &KustomizeBuilder, dirs: &[PathBuf]) -> Result<Vec<String>> {
+    tokio::select! {
+        built = builder.build_all(dirs) => Ok(built?),
+        _ = tokio::signal::ctrl_c() => anyhow::bail!("Interrupted, kustomize builds cancelled"),
+    }
+}
+
+/// Parses an overlay's rendered output and records each workload's name without
+/// the overlay's namePrefix/nameSuffix.
+fn parse_overlay(parser: &K8sParser, dir: &Path, yaml_content: &str) -> Result<Vec<K8sResource>> {
+    let mut parsed = parser.parse_yaml(yaml_content)
+        .with_context(|| format!("Failed to parse Kubernetes YAML from {}", dir.display()))?;
+    let affixes = NameAffixes::collect(dir);
+    for resource in parsed.iter_mut() {
+        resource.base_name = affixes.strip(&resource.name);
+    }
+    Ok(parsed)
+}
@@ -1,15 +1,32 @@
-use crate::checker::{CheckResult, CheckStatus};
-use crate::cli::OutputFormat;
-use anyhow::Result;
+use crate::baseline::Fingerprint;
+use crate::checker::{CheckResult, CheckStatus, CoverageFinding, CoverageKind};
+use crate::config::Severity;
+use crate::k8s::Origin;
+use crate::error::Result;
 use colored::*;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Write;
 
+/// Document printed by `--output json`.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JsonOutput {
     pub status: String,
     pub summary: Summary,
     pub applications: Vec<ApplicationResult>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub coverage: Vec<CoverageResult>,
+    /// Baseline entries that no longer occur.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixed: Vec<Fingerprint>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CoverageResult {
+    pub kind: String,
+    pub name: String,
+    pub severity: Severity,
+    pub message: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -22,31 +39,89 @@ pub struct Summary {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ApplicationResult {
     pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub application: Option<String>,
     pub status: String,
     pub missing_required: Vec<String>,
     pub missing_optional: Vec<String>,
     pub using_defaults: Vec<String>,
     pub extra_vars: Vec<String>,
+    pub validation_errors: Vec<ValidationErrorResult>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rule_violations: Vec<RuleViolationResult>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub locations: BTreeMap<String, Origin>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suppressed: Vec<SuppressedResult>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub baselined: Vec<Fingerprint>,
+    /// Configured severities of missing variables that differ from the default.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub severities: BTreeMap<String, Severity>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SuppressedResult {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub var_name: Option<String>,
+    pub message: String,
+    pub annotation: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ValidationErrorResult {
+    pub var_name: String,
+    pub message: String,
+    pub severity: Severity,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<Origin>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RuleViolationResult {
+    pub message: String,
+    pub severity: Severity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// Renders check results as colored text or as [`JsonOutput`].
 pub struct OutputFormatter {
     show_extra_vars: bool,
     verbose: bool,
+    fixed: Vec<Fingerprint>,
 }
 
 impl OutputFormatter {
     pub fn new(show_extra_vars: bool, verbose: bool) -> Self {
-        Self { show_extra_vars, verbose }
+        Self {
+            show_extra_vars,
+            verbose,
+            fixed: Vec::new(),
+        }
+    }
+
+    /// Baseline entries that no longer occur, from [`Baseline::fixed`](crate::baseline::Baseline::fixed).
+    pub fn with_fixed(mut self, fixed: Vec<Fingerprint>) -> Self {
+        self.fixed = fixed;
+        self
     }
 
-    pub fn format(&self, results: &[CheckResult], format: &OutputFormat) -> Result<String> {
+    pub fn format(&self, results: &[CheckResult], coverage: &[CoverageFinding], format: &OutputFormat) -> Result<String> {
         match format {
-            OutputFormat::Text => self.format_text(results),
-            OutputFormat::Json => self.format_json(results),
+            OutputFormat::Text => Ok(self.format_text(results, coverage).expect("writing to a String cannot fail")),
+            OutputFormat::Json => self.format_json(results, coverage),
         }
     }
 
-    fn format_text(&self, results: &[CheckResult]) -> Result<String> {
+    fn format_text(&self, results: &[CheckResult], coverage: &[CoverageFinding]) -> Result<String, std::fmt::Error> {
         let mut output = String::new();
 
         writeln!(&mut output, "{}", "Environment Variable Check Results".bold())?;
@@ -54,7 +129,16 @@ impl OutputFormatter {
         writeln!(&mut output)?;
 
         for result in results {
-            writeln!(&mut output, "{} {}", "Application:".bold(), result.app_name)?;
+            let qualified_name = match &result.namespace {
+                Some(namespace) => format!("{}/{}", namespace, result.app_name),
+                None => result.app_name.clone(),
+            };
+            match &result.application {
+                Some(app) if *app != result.app_name => {
+                    writeln!(&mut output, "{} {} ({})", "Application:".bold(), qualified_name, app)?
+                }
+                _ => writeln!(&mut output, "{} {}", "Application:".bold(), qualified_name)?,
+            }
             
             let status_icon = match result.status {
                 CheckStatus::Passed => "✓".green(),
@@ -67,21 +151,23 @@ impl OutputFormatter {
             if self.verbose && !result.passed_vars.is_empty() {
                 writeln!(&mut output, "  {} Passed variables:", "✓".green())?;
                 for (name, value) in &result.passed_vars {
-                    writeln!(&mut output, "    - {}: {}", name, value.green())?;
+                    writeln!(&mut output, "    - {}: {}{}", name, value.green(), location_suffix(&result.var_origins, name))?;
                 }
             }
             
-            if !result.missing_required.is_empty() {
+            let missing_required: Vec<&String> = counted(&result.missing_required, &result.severities).collect();
+            if !missing_required.is_empty() {
                 writeln!(&mut output, "  {} Missing required variables:", "✗".red())?;
-                for var in &result.missing_required {
-                    writeln!(&mut output, "    - {}", var.red())?;
+                for var in missing_required {
+                    writeln!(&mut output, "    - {}{}", var.red(), severity_suffix(&result.severities, var))?;
                 }
             }
             
-            if !result.missing_optional.is_empty() {
+            let missing_optional: Vec<&String> = counted(&result.missing_optional, &result.severities).collect();
+            if !missing_optional.is_empty() {
                 writeln!(&mut output, "  {} Missing optional variables:", "⚠".yellow())?;
-                for var in &result.missing_optional {
-                    writeln!(&mut output, "    - {}", var.yellow())?;
+                for var in missing_optional {
+                    writeln!(&mut output, "    - {}{}", var.yellow(), severity_suffix(&result.severities, var))?;
                 }
             }
             
@@ -95,28 +181,107 @@ impl OutputFormatter {
             if self.show_extra_vars && !result.extra_vars.is_empty() {
                 writeln!(&mut output, "  {} Extra variables (not in config):", "ℹ".blue())?;
                 for var in &result.extra_vars {
-                    writeln!(&mut output, "    - {}", var)?;
+                    writeln!(&mut output, "    - {}{}", var, location_suffix(&result.var_origins, var))?;
                 }
             }
             
-            if !result.validation_errors.is_empty() {
+            let validation_errors: Vec<_> =
+                result.validation_errors.iter().filter(|e| e.severity != Severity::Info).collect();
+            if !validation_errors.is_empty() {
                 writeln!(&mut output, "  {} Validation errors:", "✗".red())?;
-                for error in &result.validation_errors {
-                    writeln!(&mut output, "    - {}: {}", error.var_name, error.message)?;
+                for error in validation_errors {
+                    writeln!(
+                        &mut output,
+                        "    - {}: {}{}{}",
+                        error.var_name,
+                        error.message,
+                        severity_label(error.severity, Severity::Error),
+                        location_suffix(&result.var_origins, &error.var_name)
+                    )?;
+                }
+            }
+
+            let rule_violations: Vec<_> =
+                result.rule_violations.iter().filter(|v| v.severity != Severity::Info).collect();
+            if !rule_violations.is_empty() {
+                writeln!(&mut output, "  {} Rule violations:", "✗".red())?;
+                for violation in rule_violations {
+                    writeln!(
+                        &mut output,
+                        "    - {}{}",
+                        violation.message,
+                        severity_label(violation.severity, Severity::Error)
+                    )?;
+                }
+            }
+
+            let info = info_findings(result);
+            if !info.is_empty() {
+                writeln!(&mut output, "  {} Info:", "ℹ".blue())?;
+                for line in info {
+                    writeln!(&mut output, "    - {}", line)?;
+                }
+            }
+
+            if self.verbose && !result.suppressed.is_empty() {
+                writeln!(&mut output, "  {} Suppressed:", "ℹ".blue())?;
+                for finding in &result.suppressed {
+                    let annotation = format!(" ({})", finding.annotation).dimmed();
+                    match &finding.var_name {
+                        Some(var) => writeln!(&mut output, "    - {}: {}{}", var, finding.message, annotation)?,
+                        None => writeln!(&mut output, "    - {}{}", finding.message, annotation)?,
+                    }
+                }
+            }
+
+            if self.verbose && !result.baselined.is_empty() {
+                writeln!(&mut output, "  {} In baseline:", "ℹ".blue())?;
+                for fingerprint in &result.baselined {
+                    match (&fingerprint.var, &fingerprint.message) {
+                        (Some(var), _) => writeln!(&mut output, "    - {} ({})", var, fingerprint.rule)?,
+                        (None, Some(message)) => writeln!(&mut output, "    - {} ({})", message, fingerprint.rule)?,
+                        (None, None) => writeln!(&mut output, "    - {}", fingerprint.rule)?,
+                    }
                 }
             }
             
             writeln!(&mut output)?;
         }
 
+        if !coverage.is_empty() {
+            writeln!(&mut output, "{}", "Coverage".bold())?;
+            writeln!(&mut output, "{}", "-".repeat(50))?;
+            for finding in coverage {
+                let icon = match finding.severity {
+                    Severity::Error => "✗".red(),
+                    Severity::Warning => "⚠".yellow(),
+                    Severity::Info | Severity::Off => "ℹ".blue(),
+                };
+                writeln!(&mut output, "  {} {}", icon, finding.message)?;
+            }
+            writeln!(&mut output)?;
+        }
+
+        if !self.fixed.is_empty() {
+            writeln!(&mut output, "{}", "Fixed since baseline".bold())?;
+            writeln!(&mut output, "{}", "-".repeat(50))?;
+            for fingerprint in &self.fixed {
+                writeln!(&mut output, "  {} {}", "✓".green(), fingerprint)?;
+            }
+            writeln!(&mut output, "  Write the baseline again to drop them.")?;
+            writeln!(&mut output)?;
+        }
+
         writeln!(&mut output, "{}", "Summary".bold())?;
         writeln!(&mut output, "{}", "-".repeat(50))?;
         
         let total_apps = results.len();
         let failed_apps = results.iter().filter(|r| matches!(r.status, CheckStatus::Failed)).count();
         let warning_apps = results.iter().filter(|r| matches!(r.status, CheckStatus::Warning)).count();
-        let total_missing_required: usize = results.iter().map(|r| r.missing_required.len()).sum();
-        let total_missing_optional: usize = results.iter().map(|r| r.missing_optional.len()).sum();
+        let total_missing_required: usize =
+            results.iter().map(|r| counted(&r.missing_required, &r.severities).count()).sum();
+        let total_missing_optional: usize =
+            results.iter().map(|r| counted(&r.missing_optional, &r.severities).count()).sum();
         
         writeln!(&mut output, "Total applications: {}", total_apps)?;
         writeln!(&mut output, "Failed: {} | Warnings: {} | Passed: {}", 
@@ -126,17 +291,27 @@ impl OutputFormatter {
         )?;
         writeln!(&mut output, "Missing required variables: {}", total_missing_required.to_string().red())?;
         writeln!(&mut output, "Missing optional variables: {}", total_missing_optional.to_string().yellow())?;
+        let total_baselined: usize = results.iter().map(|r| r.baselined.len()).sum();
+        if total_baselined > 0 || !self.fixed.is_empty() {
+            writeln!(&mut output, "In baseline: {} | Fixed: {}", total_baselined, self.fixed.len().to_string().green())?;
+        }
 
         Ok(output)
     }
 
-    fn format_json(&self, results: &[CheckResult]) -> Result<String> {
-        let total_missing_required: usize = results.iter().map(|r| r.missing_required.len()).sum();
-        let total_missing_optional: usize = results.iter().map(|r| r.missing_optional.len()).sum();
+    fn format_json(&self, results: &[CheckResult], coverage: &[CoverageFinding]) -> Result<String> {
+        let total_missing_required: usize =
+            results.iter().map(|r| counted(&r.missing_required, &r.severities).count()).sum();
+        let total_missing_optional: usize =
+            results.iter().map(|r| counted(&r.missing_optional, &r.severities).count()).sum();
         
-        let overall_status = if results.iter().any(|r| matches!(r.status, CheckStatus::Failed)) {
+        let overall_status = if results.iter().any(|r| matches!(r.status, CheckStatus::Failed))
+            || coverage.iter().any(|f| f.severity == Severity::Error)
+        {
             "failed"
-        } else if results.iter().any(|r| matches!(r.status, CheckStatus::Warning)) {
+        } else if results.iter().any(|r| matches!(r.status, CheckStatus::Warning))
+            || coverage.iter().any(|f| f.severity == Severity::Warning)
+        {
             "warning"
         } else {
             "passed"
@@ -153,15 +328,173 @@ impl OutputFormatter {
                 .iter()
                 .map(|r| ApplicationResult {
                     name: r.app_name.clone(),
+                    namespace: r.namespace.clone(),
+                    application: r.application.clone(),
                     status: format!("{:?}", r.status).to_lowercase(),
                     missing_required: r.missing_required.clone(),
                     missing_optional: r.missing_optional.clone(),
                     using_defaults: r.using_defaults.clone(),
                     extra_vars: if self.show_extra_vars { r.extra_vars.clone() } else { vec![] },
+                    validation_errors: r
+                        .validation_errors
+                        .iter()
+                        .map(|e| ValidationErrorResult {
+                            var_name: e.var_name.clone(),
+                            message: e.message.clone(),
+                            severity: e.severity,
+                            location: r.var_origins.get(&e.var_name).cloned(),
+                        })
+                        .collect(),
+                    rule_violations: r
+                        .rule_violations
+                        .iter()
+                        .map(|v| RuleViolationResult {
+                            message: v.message.clone(),
+                            severity: v.severity,
+                        })
+                        .collect(),
+                    locations: r.var_origins.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+                    suppressed: r
+                        .suppressed
+                        .iter()
+                        .map(|f| SuppressedResult {
+                            var_name: f.var_name.clone(),
+                            message: f.message.clone(),
+                            annotation: f.annotation.to_string(),
+                        })
+                        .collect(),
+                    baselined: r.baselined.clone(),
+                    severities: r.severities.iter().map(|(k, v)| (k.clone(), *v)).collect(),
                 })
                 .collect(),
+            coverage: coverage
+                .iter()
+                .map(|f| CoverageResult {
+                    kind: match f.kind {
+                        CoverageKind::MissingApplication => "missing_application",
+                        CoverageKind::UnconfiguredWorkload => "unconfigured_workload",
+                    }
+                    .to_string(),
+                    name: f.name.clone(),
+                    severity: f.severity,
+                    message: f.message.clone(),
+                })
+                .collect(),
+            fixed: self.fixed.clone(),
         };
 
         Ok(serde_json::to_string_pretty(&json_output)?)
     }
-}
\ No newline at end of file
+}
+
+/// Missing variables that count against the workload, leaving out those
+/// reported at `info`.
+fn counted<'a>(vars: &'a [String], severities: &'a HashMap<String, Severity>) -> impl Iterator<Item = &'a String> {
+    vars.iter().filter(|var| severities.get(*var) != Some(&Severity::Info))
+}
+
+/// Lines for the findings of `result` reported at `info`, which are listed
+/// apart from failures and warnings.
+fn info_findings(result: &CheckResult) -> Vec<String> {
+    let is_info = |var: &String| result.severities.get(var) == Some(&Severity::Info);
+    let mut lines = Vec::new();
+    for var in result.missing_required.iter().filter(|var| is_info(var)) {
+        lines.push(format!("{}: required variable is missing", var));
+    }
+    for var in result.missing_optional.iter().filter(|var| is_info(var)) {
+        lines.push(format!("{}: optional variable is missing", var));
+    }
+    for error in result.validation_errors.iter().filter(|e| e.severity == Severity::Info) {
+        lines.push(format!(
+            "{}: {}{}",
+            error.var_name,
+            error.message,
+            location_suffix(&result.var_origins, &error.var_name)
+        ));
+    }
+    for violation in result.rule_violations.iter().filter(|v| v.severity == Severity::Info) {
+        lines.push(violation.message.clone());
+    }
+    lines
+}
+
+fn location_suffix(origins: &HashMap<String, Origin>, var_name: &str) -> String {
+    match origins.get(var_name) {
+        Some(origin) => format!(" ({}:{})", origin.file, origin.line).dimmed().to_string(),
+        None => String::new(),
+    }
+}
+
+fn severity_suffix(severities: &HashMap<String, Severity>, var_name: &str) -> String {
+    match severities.get(var_name) {
+        Some(severity) => format!(" [{}]", format!("{:?}", severity).to_lowercase()),
+        None => String::new(),
+    }
+}
+
+/// Like [`severity_suffix`], for a finding that carries its severity.
+fn severity_label(severity: Severity, default: Severity) -> String {
+    if severity == default {
+        return String::new();
+    }
+    format!(" [{}]", format!("{:?}", severity).to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::EnvChecker;
+    use crate::config::Config;
+    use crate::k8s::K8sParser;
+    use std::path::Path;
+
+    const CONFIG: &str = "\
+environments:
+  production:
+    required_vars:
+      - name: PORT
+        description: HTTP port
+      - name: FEATURE_URL
+        description: Feature flag service
+        severity: info
+";
+
+    const DEPLOYMENT: &str = "\
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: web
+spec:
+  template:
+    spec:
+      containers:
+        - name: web
+          env:
+            - name: PORT
+              value: \"8080\"
+";
+
+    fn results() -> Vec<CheckResult> {
+        let config = Config::lint(Path::new("envcheck.yaml"), CONFIG).unwrap();
+        let resources = K8sParser::new().parse_yaml(DEPLOYMENT).unwrap();
+        EnvChecker::new(config).check_resources(&resources, "production")
+    }
+
+    #[test]
+    fn info_findings_are_listed_apart_and_not_counted() {
+        colored::control::set_override(false);
+        let results = results();
+        assert!(matches!(results[0].status, CheckStatus::Passed));
+
+        let text = OutputFormatter::new(false, false).format(&results, &[], &OutputFormat::Text).unwrap();
+        assert!(text.contains("  ℹ Info:\n    - FEATURE_URL: required variable is missing\n"), "{}", text);
+        assert!(!text.contains("Missing required variables:\n"), "{}", text);
+        assert!(text.contains("Missing required variables: 0\n"), "{}", text);
+
+        let json = OutputFormatter::new(false, false).format(&results, &[], &OutputFormat::Json).unwrap();
+        let json: JsonOutput = serde_json::from_str(&json).unwrap();
+        assert_eq!(json.status, "passed");
+        assert_eq!(json.summary.missing_required, 0);
+        assert_eq!(json.applications[0].severities.get("FEATURE_URL"), Some(&Severity::Info));
+    }
+}
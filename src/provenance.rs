@@ -0,0 +1,402 @@
+use crate::k8s::{K8sResource, Origin};
+use regex::Regex;
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Resolves where each environment variable was introduced or last modified,
+/// using the origin and transformation annotations kustomize adds when the
+/// overlay sets `buildMetadata: [originAnnotations, transformerAnnotations]`.
+/// Resources without those annotations are left untouched.
+pub struct ProvenanceResolver {
+    overlay: PathBuf,
+    files: HashMap<PathBuf, Option<String>>,
+}
+
+impl ProvenanceResolver {
+    pub fn new(overlay: &Path) -> Self {
+        Self {
+            overlay: overlay.to_path_buf(),
+            files: HashMap::new(),
+        }
+    }
+
+    pub fn resolve(&mut self, resources: &mut [K8sResource]) {
+        for resource in resources.iter_mut() {
+            if resource.origin_path.is_none() && resource.patched_in.is_empty() {
+                continue;
+            }
+
+            let patches: Vec<PatchSource> = resource
+                .patched_in
+                .iter()
+                .flat_map(|kustomization| self.patches(&normalize(&self.overlay.join(kustomization))))
+                .filter(|patch| patch.target.as_ref().is_none_or(|target| target.matches(resource)))
+                .collect();
+            let origin_file = resource.origin_path.as_ref().map(|p| normalize(&self.overlay.join(p)));
+
+            let mut origins = Vec::new();
+            for (index, container) in resource.containers.iter().enumerate() {
+                for var_name in container.env_vars.keys() {
+                    let pattern = Regex::new(&format!(
+                        r#"(^|[\s{{,-])name:\s*["']?{}["']?\s*($|[,}}])"#,
+                        regex::escape(var_name)
+                    ))
+                    .expect("escaped variable name forms a valid pattern");
+
+                    // Later patches win over earlier ones, and any patch wins over the base resource.
+                    let origin = patches
+                        .iter()
+                        .rev()
+                        .find_map(|patch| self.locate_in_patch(patch, resource, &pattern))
+                        .or_else(|| {
+                            let file = origin_file.as_ref()?;
+                            let content = self.read(file)?;
+                            let line = find_line(content, Some(resource), true, &pattern)?;
+                            Some(self.origin(file, line))
+                        });
+                    if let Some(origin) = origin {
+                        origins.push((index, var_name.clone(), origin));
+                    }
+                }
+            }
+            for (index, var_name, origin) in origins {
+                resource.containers[index].env_origins.insert(var_name, origin);
+            }
+        }
+    }
+
+    /// Lists the patches of a kustomization in the order kustomize applies them.
+    fn patches(&mut self, kustomization: &Path) -> Vec<PatchSource> {
+        let mut patches = Vec::new();
+        let Some(Ok(value)) = self.read(kustomization).map(serde_yaml::from_str::<Value>) else {
+            return patches;
+        };
+        let dir = kustomization.parent().unwrap_or(Path::new(""));
+
+        if let Some(paths) = value.get("patchesStrategicMerge").and_then(|p| p.as_sequence()) {
+            for path in paths.iter().filter_map(|p| p.as_str()) {
+                patches.push(if path.contains('\n') {
+                    PatchSource::inline(kustomization, path, None)
+                } else {
+                    PatchSource::file(normalize(&dir.join(path)), None)
+                });
+            }
+        }
+        for key in ["patchesJson6902", "patches"] {
+            let Some(entries) = value.get(key).and_then(|p| p.as_sequence()) else {
+                continue;
+            };
+            for entry in entries {
+                let target = entry.get("target").map(|target| PatchTarget {
+                    kind: target.get("kind").and_then(|k| k.as_str()).map(str::to_string),
+                    name: target.get("name").and_then(|n| n.as_str()).map(str::to_string),
+                });
+                if let Some(path) = entry.get("path").and_then(|p| p.as_str()) {
+                    patches.push(PatchSource::file(normalize(&dir.join(path)), target));
+                } else if let Some(patch) = entry.get("patch").and_then(|p| p.as_str()) {
+                    patches.push(PatchSource::inline(kustomization, patch, target));
+                }
+            }
+        }
+
+        patches
+    }
+
+    /// Finds the line of `patch` that declares an env entry matching `pattern`.
+    /// Patches without a target only count in documents naming `resource`.
+    fn locate_in_patch(&mut self, patch: &PatchSource, resource: &K8sResource, pattern: &Regex) -> Option<Origin> {
+        let scope = patch.target.is_none().then_some(resource);
+        let content = self.read(&patch.file)?;
+        let line = match &patch.inline {
+            Some(inline) => {
+                let line = find_line(inline, scope, false, pattern)?;
+                inline_line(content, inline, line)?
+            }
+            None => find_line(content, scope, false, pattern)?,
+        };
+        Some(self.origin(&patch.file, line))
+    }
+
+    fn origin(&self, file: &Path, line: usize) -> Origin {
+        Origin {
+            file: file.display().to_string(),
+            line: line + 1,
+            overlay: self.overlay.display().to_string(),
+        }
+    }
+
+    fn read(&mut self, file: &Path) -> Option<&str> {
+        self.files
+            .entry(file.to_path_buf())
+            .or_insert_with(|| std::fs::read_to_string(file).ok())
+            .as_deref()
+    }
+}
+
+/// A patch listed by a kustomization.
+struct PatchSource {
+    /// The patch file, or the kustomization for inline patches.
+    file: PathBuf,
+    /// Text of an inline patch.
+    inline: Option<String>,
+    target: Option<PatchTarget>,
+}
+
+impl PatchSource {
+    fn file(file: PathBuf, target: Option<PatchTarget>) -> Self {
+        Self { file, inline: None, target }
+    }
+
+    fn inline(kustomization: &Path, patch: &str, target: Option<PatchTarget>) -> Self {
+        Self {
+            file: kustomization.to_path_buf(),
+            inline: Some(patch.to_string()),
+            target,
+        }
+    }
+}
+
+/// The `target` of a patch, as far as it identifies a workload.
+struct PatchTarget {
+    kind: Option<String>,
+    /// Anchored regex, as in kustomize.
+    name: Option<String>,
+}
+
+impl PatchTarget {
+    fn matches(&self, resource: &K8sResource) -> bool {
+        if self.kind.as_ref().is_some_and(|kind| *kind != resource.kind) {
+            return false;
+        }
+        let Some(name) = &self.name else {
+            return true;
+        };
+        match Regex::new(&format!("^(?:{})$", name)) {
+            Ok(regex) => regex.is_match(&resource.name) || regex.is_match(&resource.base_name),
+            Err(_) => *name == resource.name || *name == resource.base_name,
+        }
+    }
+}
+
+/// Finds the first line matching `pattern`, only searching the YAML documents that
+/// declare `resource` when one is given. With `fallback`, a file holding a single
+/// document is searched even if its name differs, as the origin annotation already
+/// tied the resource to the file.
+fn find_line(content: &str, resource: Option<&K8sResource>, fallback: bool, pattern: &Regex) -> Option<usize> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut documents = Vec::new();
+    let mut start = 0;
+    for (index, line) in lines.iter().enumerate() {
+        if *line == "---" || line.starts_with("--- ") {
+            documents.push(start..index);
+            start = index + 1;
+        }
+    }
+    documents.push(start..lines.len());
+    documents.retain(|range| lines[range.clone()].iter().any(|line| !line.trim().is_empty()));
+
+    let single = documents.len() == 1;
+    if let Some(resource) = resource {
+        documents.retain(|range| declares(&lines[range.clone()].join("\n"), resource));
+        if documents.is_empty() && fallback && single {
+            return find_line(content, None, false, pattern);
+        }
+    }
+    documents
+        .into_iter()
+        .find_map(|range| range.clone().find(|&index| pattern.is_match(lines[index])))
+}
+
+/// Whether a YAML document has the kind and name of `resource`, by its name
+/// before or after the overlays' namePrefix/nameSuffix.
+fn declares(document: &str, resource: &K8sResource) -> bool {
+    let Ok(value) = serde_yaml::from_str::<Value>(document) else {
+        return false;
+    };
+    let kind = value.get("kind").and_then(|k| k.as_str());
+    let name = value.get("metadata").and_then(|m| m.get("name")).and_then(|n| n.as_str());
+    kind == Some(resource.kind.as_str()) && name.is_some_and(|name| name == resource.name || name == resource.base_name)
+}
+
+/// Maps `line` of an inline patch to the line of `kustomization` that holds it.
+fn inline_line(kustomization: &str, inline: &str, line: usize) -> Option<usize> {
+    let file: Vec<&str> = kustomization.lines().map(str::trim).collect();
+    let patch: Vec<&str> = inline.lines().map(str::trim).collect();
+    file.windows(patch.len())
+        .position(|window| window == patch.as_slice())
+        .map(|start| start + line)
+        .or_else(|| file.iter().position(|candidate| candidate.contains(patch[line])))
+}
+
+/// Lexically resolves `.` and `..` so paths stay relative to where the tool was run.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if matches!(normalized.components().next_back(), Some(Component::Normal(_))) {
+                    normalized.pop();
+                } else {
+                    normalized.push("..");
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::k8s::K8sParser;
+    use tempfile::TempDir;
+
+    const BASE: &str = "\
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: api
+spec:
+  template:
+    spec:
+      containers:
+        - name: api
+          env:
+            - name: TZ
+              value: UTC
+---
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: web
+spec:
+  template:
+    spec:
+      containers:
+        - name: web
+          env:
+            - name: LOG_LEVEL
+              value: info
+            - name: PORT
+              value: \"8080\"
+            - name: TZ
+              value: UTC
+";
+
+    const KUSTOMIZATION: &str = "\
+resources: [../base]
+patches:
+  - path: log-level.yaml
+  - target: {kind: Deployment, name: web}
+    patch: |-
+      apiVersion: apps/v1
+      kind: Deployment
+      metadata:
+        name: web
+      spec:
+        template:
+          spec:
+            containers:
+              - name: web
+                env:
+                  - name: PORT
+                    value: \"9090\"
+  - target: {kind: Deployment, name: api}
+    patch: |-
+      apiVersion: apps/v1
+      kind: Deployment
+      metadata:
+        name: api
+      spec:
+        template:
+          spec:
+            containers:
+              - name: api
+                env:
+                  - name: PORT
+                    value: \"9191\"
+";
+
+    const LOG_LEVEL_PATCH: &str = "\
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: web
+spec:
+  template:
+    spec:
+      containers:
+        - name: web
+          env:
+            - name: LOG_LEVEL
+              value: debug
+";
+
+    /// `web` as kustomize renders it with origin and transformer annotations.
+    const RENDERED: &str = "\
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: web
+  annotations:
+    config.kubernetes.io/origin: |
+      path: ../base/deployment.yaml
+    alpha.config.kubernetes.io/transformations: |
+      - configuredIn: kustomization.yaml
+        configuredBy:
+          apiVersion: builtin
+          kind: PatchTransformer
+spec:
+  template:
+    spec:
+      containers:
+        - name: web
+          env:
+            - name: LOG_LEVEL
+              value: debug
+            - name: PORT
+              value: \"9090\"
+            - name: TZ
+              value: UTC
+";
+
+    #[test]
+    fn variables_map_to_base_and_patch_lines() {
+        let dir = TempDir::new().unwrap();
+        for (path, content) in [
+            ("base/deployment.yaml", BASE),
+            ("overlay/kustomization.yaml", KUSTOMIZATION),
+            ("overlay/log-level.yaml", LOG_LEVEL_PATCH),
+        ] {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        let overlay = dir.path().join("overlay");
+        let mut resources = K8sParser::new().parse_yaml(RENDERED).unwrap();
+        ProvenanceResolver::new(&overlay).resolve(&mut resources);
+
+        let origins = &resources[0].containers[0].env_origins;
+        let location = |var: &str| {
+            let origin = &origins[var];
+            assert_eq!(origin.overlay, overlay.display().to_string());
+            (origin.file.clone(), origin.line)
+        };
+        let file = |path: &str| dir.path().join(path).display().to_string();
+        // The base file declares TZ for api first; web's own document is used.
+        assert_eq!(location("TZ"), (file("base/deployment.yaml"), 28));
+        assert_eq!(location("LOG_LEVEL"), (file("overlay/log-level.yaml"), 11));
+        // The later inline patch targets api, so web's PORT comes from the first one.
+        assert_eq!(location("PORT"), (file("overlay/kustomization.yaml"), 16));
+    }
+
+    #[test]
+    fn resources_without_annotations_are_untouched() {
+        let mut resources = K8sParser::new().parse_yaml(BASE).unwrap();
+        ProvenanceResolver::new(Path::new("overlay")).resolve(&mut resources);
+        assert!(resources.iter().all(|r| r.containers.iter().all(|c| c.env_origins.is_empty())));
+    }
+}
@@ -0,0 +1,762 @@
+use crate::config::{BoundField, Config, EnvVar, Rule as ConfigRule, Severity};
+use crate::expansion;
+use crate::expr::{Expression, Value};
+use crate::k8s::{ContainerInfo, K8sResource};
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+
+/// What a check is given about one workload.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct RuleContext<'a> {
+    pub resource: &'a K8sResource,
+    pub environment: &'a str,
+    /// Entry under `applications` the workload was matched to, if any.
+    pub application: Option<&'a str>,
+    pub config: &'a Config,
+    /// Variables set by any container of the workload; later containers win.
+    pub vars: &'a HashMap<String, String>,
+    /// Required variables that apply to the workload's application and namespace.
+    pub required_vars: &'a [&'a EnvVar],
+    /// Optional variables that apply to the workload's application and namespace.
+    pub optional_vars: &'a [&'a EnvVar],
+}
+
+impl RuleContext<'_> {
+    /// Configured defaults of the variables that apply to the workload.
+    pub fn defaults(&self) -> HashMap<&str, &str> {
+        self.required_vars
+            .iter()
+            .chain(self.optional_vars)
+            .filter_map(|v| Some((v.name.as_str(), v.default.as_deref()?)))
+            .collect()
+    }
+}
+
+/// Something a check reports about a workload.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Finding {
+    /// A required variable is not set; fails the workload.
+    MissingRequired(String),
+    /// An optional variable without a default is not set; warns.
+    MissingOptional(String),
+    /// An optional variable is not set, so its default applies.
+    UsingDefault(String),
+    /// A variable is set that the configuration does not mention.
+    Extra(String),
+    /// A variable that was checked and found acceptable.
+    Passed { var_name: String, value: String },
+    /// A variable has an unacceptable value; fails the workload.
+    Invalid { var_name: String, message: String },
+    /// A requirement that does not hold; fails the workload.
+    Violation(String),
+}
+
+/// A check run against every workload, identified by a stable ID so it can be
+/// disabled.
+///
+/// Implement [`check`](Rule::check) to look at the workload as a whole, or
+/// [`check_container`](Rule::check_container) to look at each container in turn.
+pub trait Rule: Send + Sync {
+    /// Short kebab-case identifier, e.g. `required-vars`.
+    fn id(&self) -> &str;
+
+    /// One-line summary shown by `kustomize-envcheck rules`.
+    fn description(&self) -> &str;
+
+    /// Severity of the check's failures unless the configuration sets one; `None`
+    /// uses the default for each kind of finding.
+    fn default_severity(&self) -> Option<Severity> {
+        None
+    }
+
+    fn check(&self, context: &RuleContext<'_>) -> Vec<Finding> {
+        context
+            .resource
+            .containers
+            .iter()
+            .flat_map(|container| self.check_container(context, container))
+            .collect()
+    }
+
+    fn check_container(&self, _context: &RuleContext<'_>, _container: &ContainerInfo) -> Vec<Finding> {
+        Vec::new()
+    }
+}
+
+/// IDs of the checks [`RuleRegistry::builtin`] registers, in order.
+pub const BUILTIN_RULE_IDS: &[&str] = &[
+    "required-vars",
+    "pattern",
+    "optional-vars",
+    "extra-vars",
+    "conditional-rules",
+    "expression-rules",
+    "var-references",
+    "duplicate-env",
+    "env-from-overrides",
+    "port-bindings",
+];
+
+/// The checks an [`EnvChecker`](crate::EnvChecker) runs, in order, and which of them are enabled.
+pub struct RuleRegistry {
+    rules: Vec<(Box<dyn Rule>, bool)>,
+}
+
+impl RuleRegistry {
+    /// A registry without any checks.
+    pub fn empty() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// The built-in checks, with patterns and expressions from `config` compiled up front.
+    pub fn builtin(config: &Config) -> Self {
+        let mut registry = Self::empty();
+        registry.register(RequiredVars);
+        registry.register(Patterns::new(config));
+        registry.register(OptionalVars);
+        registry.register(ExtraVars);
+        registry.register(ConditionalRules);
+        registry.register(ExpressionRules::new(config));
+        registry.register(VarReferences);
+        registry.register(DuplicateEnv);
+        registry.register(EnvFromOverrides);
+        registry.register(PortBindings);
+        registry
+    }
+
+    /// Registers a check for each module under `plugins` in `config`, in ID order.
+    #[cfg(feature = "wasm-plugins")]
+    pub fn register_plugins(&mut self, config: &Config) -> crate::error::Result<()> {
+        let mut ids: Vec<&String> = config.plugins.keys().collect();
+        ids.sort();
+        for id in ids {
+            self.register(crate::wasm_plugin::WasmRule::load(id, &config.plugins[id])?);
+        }
+        Ok(())
+    }
+
+    /// Adds an enabled check, replacing any check with the same ID in place.
+    pub fn register(&mut self, rule: impl Rule + 'static) {
+        match self.rules.iter_mut().find(|(existing, _)| existing.id() == rule.id()) {
+            Some(slot) => *slot = (Box::new(rule), true),
+            None => self.rules.push((Box::new(rule), true)),
+        }
+    }
+
+    /// Returns `false` if no check has this ID.
+    pub fn enable(&mut self, id: &str) -> bool {
+        self.set_enabled(id, true)
+    }
+
+    /// Returns `false` if no check has this ID.
+    pub fn disable(&mut self, id: &str) -> bool {
+        self.set_enabled(id, false)
+    }
+
+    fn set_enabled(&mut self, id: &str, enabled: bool) -> bool {
+        match self.rules.iter_mut().find(|(rule, _)| rule.id() == id) {
+            Some((_, state)) => {
+                *state = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_enabled(&self, id: &str) -> bool {
+        self.rules.iter().any(|(rule, enabled)| *enabled && rule.id() == id)
+    }
+
+    /// Every registered check, enabled or not, in the order they run.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Rule> {
+        self.rules.iter().map(|(rule, _)| rule.as_ref())
+    }
+
+    pub(crate) fn enabled(&self) -> impl Iterator<Item = &dyn Rule> {
+        self.rules.iter().filter(|(_, enabled)| *enabled).map(|(rule, _)| rule.as_ref())
+    }
+}
+
+/// Required variables must be set.
+struct RequiredVars;
+
+impl Rule for RequiredVars {
+    fn id(&self) -> &str {
+        "required-vars"
+    }
+
+    fn description(&self) -> &str {
+        "Variables under required_vars and additional_vars must be set"
+    }
+
+    fn check(&self, context: &RuleContext<'_>) -> Vec<Finding> {
+        context
+            .required_vars
+            .iter()
+            .map(|var| match context.vars.get(&var.name) {
+                Some(value) => Finding::Passed {
+                    var_name: var.name.clone(),
+                    value: value.clone(),
+                },
+                None => Finding::MissingRequired(var.name.clone()),
+            })
+            .collect()
+    }
+}
+
+/// Required variables that are set must match their `pattern`.
+struct Patterns {
+    /// Every variable pattern, compiled once up front.
+    patterns: HashMap<String, Regex>,
+}
+
+impl Patterns {
+    fn new(config: &Config) -> Self {
+        let patterns = config
+            .environments
+            .values()
+            .flat_map(|env| env.required_vars.iter().chain(&env.optional_vars))
+            .chain(config.applications.values().flat_map(|app| &app.additional_vars))
+            .filter_map(|var| var.pattern.as_ref())
+            .filter_map(|pattern| Some((pattern.clone(), Regex::new(pattern).ok()?)))
+            .collect();
+        Self { patterns }
+    }
+}
+
+impl Rule for Patterns {
+    fn id(&self) -> &str {
+        "pattern"
+    }
+
+    fn description(&self) -> &str {
+        "Required variables must match their pattern"
+    }
+
+    fn check(&self, context: &RuleContext<'_>) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for var in context.required_vars {
+            let (Some(value), Some(pattern)) = (context.vars.get(&var.name), &var.pattern) else {
+                continue;
+            };
+            // Config::from_path rejects invalid patterns, so a miss here is unexpected.
+            let message = match self.patterns.get(pattern) {
+                Some(regex) if regex.is_match(value) => continue,
+                Some(_) => format!("Value '{}' does not match pattern '{}'", value, pattern),
+                None => format!("Invalid regex pattern: {}", pattern),
+            };
+            findings.push(Finding::Invalid {
+                var_name: var.name.clone(),
+                message,
+            });
+        }
+        findings
+    }
+}
+
+/// Unset optional variables warn unless they have a default.
+struct OptionalVars;
+
+impl Rule for OptionalVars {
+    fn id(&self) -> &str {
+        "optional-vars"
+    }
+
+    fn description(&self) -> &str {
+        "Variables under optional_vars without a default should be set"
+    }
+
+    fn check(&self, context: &RuleContext<'_>) -> Vec<Finding> {
+        context
+            .optional_vars
+            .iter()
+            .map(|var| match context.vars.get(&var.name) {
+                Some(value) => Finding::Passed {
+                    var_name: var.name.clone(),
+                    value: value.clone(),
+                },
+                None if var.default.is_some() => Finding::UsingDefault(var.name.clone()),
+                None => Finding::MissingOptional(var.name.clone()),
+            })
+            .collect()
+    }
+}
+
+/// Variables the configuration does not mention, shown with `--show-extra-vars`.
+struct ExtraVars;
+
+impl Rule for ExtraVars {
+    fn id(&self) -> &str {
+        "extra-vars"
+    }
+
+    fn description(&self) -> &str {
+        "Lists variables that are set but not configured"
+    }
+
+    fn check(&self, context: &RuleContext<'_>) -> Vec<Finding> {
+        let expected = |name: &String| {
+            context.required_vars.iter().chain(context.optional_vars).any(|var| var.name == *name)
+        };
+        context
+            .vars
+            .keys()
+            .filter(|name| !expected(name))
+            .map(|name| Finding::Extra(name.clone()))
+            .collect()
+    }
+}
+
+/// `requires`, `one_of` and `mutually_exclusive` under `rules`.
+struct ConditionalRules;
+
+impl Rule for ConditionalRules {
+    fn id(&self) -> &str {
+        "conditional-rules"
+    }
+
+    fn description(&self) -> &str {
+        "requires, one_of and mutually_exclusive entries under rules must hold"
+    }
+
+    fn check(&self, context: &RuleContext<'_>) -> Vec<Finding> {
+        let defaults = context.defaults();
+        let vars = context.vars;
+        let mut findings = Vec::new();
+        for rule in context.config.get_rules(context.application, context.environment) {
+            let Some(because) = rule_condition(rule, vars, &defaults) else {
+                continue;
+            };
+            if let Some(message) = check_rule(rule, vars, &because) {
+                findings.push(Finding::Violation(rule.message.clone().unwrap_or(message)));
+            }
+        }
+        findings
+    }
+}
+
+/// Returns why `rule` does not hold; `because` names the condition that made it apply.
+fn check_rule(rule: &ConfigRule, vars: &HashMap<String, String>, because: &str) -> Option<String> {
+    let missing: Vec<&str> = rule.requires.iter().filter(|v| !vars.contains_key(*v)).map(String::as_str).collect();
+    if !missing.is_empty() {
+        let verb = if missing.len() == 1 { "is" } else { "are" };
+        return Some(format!("{} {} required{}", missing.join(", "), verb, because));
+    }
+
+    if !rule.one_of.is_empty() {
+        let set: Vec<&str> = rule.one_of.iter().filter(|v| vars.contains_key(*v)).map(String::as_str).collect();
+        if set.len() != 1 {
+            let found = if set.is_empty() { "none is set".to_string() } else { format!("{} are set", set.join(", ")) };
+            return Some(format!("Exactly one of {} must be set{}, but {}", rule.one_of.join(", "), because, found));
+        }
+    }
+
+    let set: Vec<&str> = rule.mutually_exclusive.iter().filter(|v| vars.contains_key(*v)).map(String::as_str).collect();
+    if set.len() > 1 {
+        return Some(format!(
+            "At most one of {} may be set{}, but {} are set",
+            rule.mutually_exclusive.join(", "),
+            because,
+            set.join(", ")
+        ));
+    }
+
+    None
+}
+
+/// `expr` entries under `rules`.
+struct ExpressionRules {
+    /// Every rule expression, parsed once up front.
+    expressions: HashMap<String, Expression>,
+}
+
+impl ExpressionRules {
+    fn new(config: &Config) -> Self {
+        let expressions = config
+            .environments
+            .values()
+            .flat_map(|env| &env.rules)
+            .chain(config.applications.values().flat_map(|app| &app.rules))
+            .filter_map(|rule| rule.expr.as_ref())
+            .filter_map(|source| Some((source.clone(), Expression::parse(source).ok()?)))
+            .collect();
+        Self { expressions }
+    }
+
+    fn check_expression(
+        &self,
+        rule: &ConfigRule,
+        source: &str,
+        bindings: &HashMap<String, Value>,
+        vars: &HashMap<String, String>,
+        because: &str,
+    ) -> Option<Finding> {
+        let expression = self.expressions.get(source);
+        let identifiers = expression.map(Expression::identifiers).unwrap_or_default();
+        // A variable that is unset without a default evaluates to null, which would
+        // only report again what required-vars already reports as missing.
+        if identifiers.iter().any(|name| !bindings.contains_key(*name)) {
+            return None;
+        }
+        // Attribute the failure to the first variable the expression reads, so it gets a location.
+        let var_name = identifiers
+            .iter()
+            .find(|name| vars.contains_key(**name))
+            .or_else(|| identifiers.iter().find(|name| !BINDINGS.contains(name)))
+            .map_or_else(|| "expr".to_string(), |name| name.to_string());
+
+        let message = match expression.map(|e| e.evaluate(bindings)) {
+            Some(Ok(Value::Bool(true))) => return None,
+            Some(Ok(Value::Bool(false))) => rule
+                .message
+                .clone()
+                .unwrap_or_else(|| format!("Expression `{}` is false{}", source, because)),
+            Some(Ok(other)) => format!("Expression `{}` must evaluate to a bool, got {}", source, other),
+            Some(Err(e)) => format!("Expression `{}` could not be evaluated: {}", source, e),
+            // Config::from_path rejects invalid expressions, so a miss here is unexpected.
+            None => format!("Invalid expression `{}`", source),
+        };
+        Some(Finding::Invalid { var_name, message })
+    }
+}
+
+impl Rule for ExpressionRules {
+    fn id(&self) -> &str {
+        "expression-rules"
+    }
+
+    fn description(&self) -> &str {
+        "expr entries under rules must evaluate to true"
+    }
+
+    fn check(&self, context: &RuleContext<'_>) -> Vec<Finding> {
+        let defaults = context.defaults();
+        let mut bindings = None;
+        let mut findings = Vec::new();
+        for rule in context.config.get_rules(context.application, context.environment) {
+            let Some(source) = &rule.expr else {
+                continue;
+            };
+            let Some(because) = rule_condition(rule, context.vars, &defaults) else {
+                continue;
+            };
+            let bindings = bindings.get_or_insert_with(|| expression_bindings(context, &defaults));
+            findings.extend(self.check_expression(rule, source, bindings, context.vars, &because));
+        }
+        findings
+    }
+}
+
+/// `$(VAR)` references in env values, command and args. Kubernetes only expands
+/// references to variables defined earlier in `env` and leaves the rest as written.
+struct VarReferences;
+
+impl Rule for VarReferences {
+    fn id(&self) -> &str {
+        "var-references"
+    }
+
+    fn description(&self) -> &str {
+        "$(VAR) references in env values, command and args must refer to earlier variables"
+    }
+
+    fn check_container(&self, _context: &RuleContext<'_>, container: &ContainerInfo) -> Vec<Finding> {
+        // Keys from envFrom sources are unknown, so any name might come from one.
+        let may_be_undefined = container.env_from_refs.is_empty();
+        let mut findings = Vec::new();
+
+        for (i, entry) in container.env.iter().enumerate() {
+            let Some(value) = &entry.value else {
+                continue;
+            };
+            for name in expansion::references(value) {
+                let message = if container.env[..i].iter().any(|e| e.name == name) {
+                    continue;
+                } else if container.env[i + 1..].iter().any(|e| e.name == name) {
+                    format!("$({}) is not expanded because {} is defined later in env", name, name)
+                } else if may_be_undefined {
+                    format!("$({}) refers to a variable that is not defined", name)
+                } else {
+                    continue;
+                };
+                findings.push(Finding::Invalid {
+                    var_name: entry.name.clone(),
+                    message,
+                });
+            }
+        }
+
+        if may_be_undefined {
+            for (field, values) in [("command", &container.command), ("args", &container.args)] {
+                for name in values.iter().flat_map(|value| expansion::references(value)) {
+                    if !container.env.iter().any(|e| e.name == name) {
+                        findings.push(Finding::Violation(format!(
+                            "$({}) in the {} of container '{}' refers to a variable that is not defined",
+                            name, field, container.name
+                        )));
+                    }
+                }
+            }
+        }
+
+        findings
+    }
+}
+
+/// A name listed more than once under a container's `env`; Kubernetes uses the last.
+struct DuplicateEnv;
+
+impl Rule for DuplicateEnv {
+    fn id(&self) -> &str {
+        "duplicate-env"
+    }
+
+    fn description(&self) -> &str {
+        "A container must not list the same variable twice under env"
+    }
+
+    fn check_container(&self, _context: &RuleContext<'_>, container: &ContainerInfo) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for (i, entry) in container.env.iter().enumerate() {
+            // Report each name once, at its first entry.
+            if container.env[..i].iter().any(|e| e.name == entry.name) {
+                continue;
+            }
+            let listed: Vec<String> = container
+                .env
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| e.name == entry.name)
+                .map(|(j, e)| match (&e.value, &e.value_from) {
+                    (Some(value), _) => format!("'{}' at env[{}]", value, j),
+                    (None, Some(source)) => format!("{} at env[{}]", source, j),
+                    (None, None) => format!("'' at env[{}]", j),
+                })
+                .collect();
+            if listed.len() > 1 {
+                findings.push(Finding::Invalid {
+                    var_name: entry.name.clone(),
+                    message: format!(
+                        "Listed {} times in env of container '{}': {}; the last one applies",
+                        listed.len(),
+                        container.name,
+                        listed.join(", ")
+                    ),
+                });
+            }
+        }
+        findings
+    }
+}
+
+/// An `env` entry that replaces a key the container also imports through `envFrom`.
+struct EnvFromOverrides;
+
+impl Rule for EnvFromOverrides {
+    fn id(&self) -> &str {
+        "env-from-overrides"
+    }
+
+    fn description(&self) -> &str {
+        "Reports env entries that override keys imported through envFrom"
+    }
+
+    fn default_severity(&self) -> Option<Severity> {
+        Some(Severity::Warning)
+    }
+
+    fn check_container(&self, _context: &RuleContext<'_>, container: &ContainerInfo) -> Vec<Finding> {
+        container
+            .env
+            .iter()
+            .enumerate()
+            .filter(|(i, entry)| !container.env[..*i].iter().any(|e| e.name == entry.name))
+            .filter_map(|(_, entry)| {
+                let source = container.env_from_keys.get(&entry.name)?;
+                // Reading the same key explicitly changes nothing.
+                if entry.value_from.as_ref() == Some(source) {
+                    return None;
+                }
+                Some(Finding::Invalid {
+                    var_name: entry.name.clone(),
+                    message: format!("Overrides {} from envFrom of container '{}'", source, container.name),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Variables with `binds` must hold the port the container spec uses.
+struct PortBindings;
+
+impl Rule for PortBindings {
+    fn id(&self) -> &str {
+        "port-bindings"
+    }
+
+    fn description(&self) -> &str {
+        "Variables with binds must match the containerPort or probe port they name"
+    }
+
+    fn check_container(&self, context: &RuleContext<'_>, container: &ContainerInfo) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for var in context.required_vars.iter().chain(context.optional_vars) {
+            let Some(binds) = &var.binds else {
+                continue;
+            };
+            // Values from valueFrom sources are not known, and patterns cover the rest.
+            let Some(value) = container.env_vars.get(&var.name).and_then(|v| v.trim().parse::<i32>().ok()) else {
+                continue;
+            };
+            for field in binds.fields() {
+                let (name, ports) = match field {
+                    BoundField::ContainerPort => ("containerPort", container.ports.clone()),
+                    BoundField::ReadinessProbe => ("readinessProbe port", Vec::from_iter(container.probe_ports.readiness)),
+                    BoundField::LivenessProbe => ("livenessProbe port", Vec::from_iter(container.probe_ports.liveness)),
+                    BoundField::StartupProbe => ("startupProbe port", Vec::from_iter(container.probe_ports.startup)),
+                };
+                // A field the container does not set has nothing to disagree with.
+                if ports.is_empty() || ports.contains(&value) {
+                    continue;
+                }
+                let ports: Vec<String> = ports.iter().map(i32::to_string).collect();
+                findings.push(Finding::Invalid {
+                    var_name: var.name.clone(),
+                    message: format!(
+                        "Value {} does not match the {} of container '{}' ({})",
+                        value,
+                        name,
+                        container.name,
+                        ports.join(", ")
+                    ),
+                });
+            }
+        }
+        findings
+    }
+}
+
+/// Returns `Some` with a " because ..." clause (empty without conditions) when
+/// every `when` condition of `rule` holds, comparing unset variables by their default.
+fn rule_condition(rule: &ConfigRule, vars: &HashMap<String, String>, defaults: &HashMap<&str, &str>) -> Option<String> {
+    let mut conditions = Vec::new();
+    for (name, expected) in &rule.when {
+        match vars.get(name) {
+            Some(value) if expected.matches(value) => conditions.push(format!("{}={}", name, value)),
+            None => match defaults.get(name.as_str()) {
+                Some(value) if expected.matches(value) => conditions.push(format!("{}={} (default)", name, value)),
+                _ => return None,
+            },
+            _ => return None,
+        }
+    }
+    if conditions.is_empty() {
+        Some(String::new())
+    } else {
+        Some(format!(" because {}", conditions.join(" and ")))
+    }
+}
+
+/// Names bound for rule expressions besides the variables themselves, which
+/// configured variables may not use.
+pub(crate) const BINDINGS: [&str; 4] = ["vars", "resource", "application", "environment"];
+
+/// Names visible to rule expressions: each variable by name (unset ones by their
+/// default), `vars` holding the variables actually set, `resource` describing the
+/// workload, `application` and `environment`.
+fn expression_bindings(context: &RuleContext<'_>, defaults: &HashMap<&str, &str>) -> HashMap<String, Value> {
+    let strings = |map: &mut dyn Iterator<Item = (&String, &String)>| {
+        Value::Map(map.map(|(k, v)| (k.clone(), Value::String(v.clone()))).collect())
+    };
+    let resource = context.resource;
+    let vars = context.vars;
+    let mut bindings: HashMap<String, Value> = defaults
+        .iter()
+        .map(|(k, v)| (k.to_string(), Value::String(v.to_string())))
+        .chain(vars.iter().map(|(k, v)| (k.clone(), Value::String(v.clone()))))
+        .collect();
+    bindings.insert("vars".to_string(), strings(&mut vars.iter()));
+    bindings.insert(
+        "resource".to_string(),
+        Value::Map(BTreeMap::from([
+            ("kind".to_string(), Value::String(resource.kind.clone())),
+            ("name".to_string(), Value::String(resource.name.clone())),
+            ("namespace".to_string(), Value::String(resource.effective_namespace().to_string())),
+            ("labels".to_string(), strings(&mut resource.labels.iter())),
+            ("annotations".to_string(), strings(&mut resource.annotations.iter())),
+        ])),
+    );
+    bindings.insert(
+        "application".to_string(),
+        context.application.map_or(Value::Null, |a| Value::String(a.to_string())),
+    );
+    bindings.insert("environment".to_string(), Value::String(context.environment.to_string()));
+    bindings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::k8s::K8sParser;
+    use std::path::Path;
+
+    const EXPRESSION_CONFIG: &str = "\
+environments:
+  production:
+    required_vars:
+      - name: PORT
+        description: HTTP port
+      - name: WORKERS
+        description: Worker threads
+        default: \"4\"
+    rules:
+      - expr: int(PORT) > 1024
+      - expr: int(WORKERS) <= 8
+";
+
+    /// Findings of the expression rules for a workload setting `env`.
+    fn expression_findings(env: &str) -> Vec<Finding> {
+        let config = Config::lint(Path::new("envcheck.yaml"), EXPRESSION_CONFIG).unwrap();
+        let yaml = format!(
+            "apiVersion: apps/v1\nkind: Deployment\nmetadata:\n  name: web\nspec:\n  template:\n    spec:\n      containers:\n        - name: web\n          env: {}\n",
+            env
+        );
+        let resource = K8sParser::new().parse_yaml(&yaml).unwrap().remove(0);
+        let vars = resource.containers[0].env_vars.clone();
+        let required_vars = config.get_required_vars(None, "production");
+        let context = RuleContext {
+            resource: &resource,
+            environment: "production",
+            application: None,
+            config: &config,
+            vars: &vars,
+            required_vars: &required_vars,
+            optional_vars: &[],
+        };
+        ExpressionRules::new(&config).check(&context)
+    }
+
+    #[test]
+    fn builtin_rule_ids_match_registry() {
+        let registry = RuleRegistry::builtin(&Config::default());
+        let ids: Vec<&str> = registry.iter().map(|rule| rule.id()).collect();
+        assert_eq!(ids, BUILTIN_RULE_IDS);
+    }
+
+    #[test]
+    fn expressions_reading_unset_variables_are_skipped() {
+        assert!(expression_findings("[]").is_empty());
+
+        let findings = expression_findings("[{name: PORT, value: \"80\"}, {name: WORKERS, value: \"16\"}]");
+        let vars: Vec<&str> = findings
+            .iter()
+            .map(|finding| match finding {
+                Finding::Invalid { var_name, .. } => var_name.as_str(),
+                other => panic!("unexpected finding {:?}", other),
+            })
+            .collect();
+        assert_eq!(vars, ["PORT", "WORKERS"]);
+    }
+}
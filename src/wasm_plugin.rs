@@ -0,0 +1,217 @@
+//! Checks implemented as WebAssembly modules listed under `plugins`. Modules run
+//! in an interpreter and may not import anything, so they have no access to the
+//! filesystem, network or clock; every call is bounded by fuel and a memory limit.
+//! Fuel is the only bound on running time, as a module cannot block on the host.
+//!
+//! A module exports `memory`, `envcheck_alloc(len: i32) -> i32` and
+//! `envcheck_check(ptr: i32, len: i32) -> i64`. For each workload the host copies
+//! an input JSON document into a buffer from `envcheck_alloc`, calls
+//! `envcheck_check` and reads the output JSON from the `ptr << 32 | len` it
+//! returns. The output is a list of `{"message": ..., "var": ...}` findings.
+
+use crate::config::Plugin;
+use crate::error::{Error, Result};
+use crate::rules::{Finding, Rule, RuleContext};
+use serde::Deserialize;
+use serde_json::json;
+use wasmi::{Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, TrapCode};
+
+/// Linear memory a module may grow to.
+const MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// A check backed by a compiled WebAssembly module. Each workload gets a fresh
+/// instance, so no state carries over between calls.
+pub struct WasmRule {
+    id: String,
+    description: String,
+    engine: Engine,
+    module: Module,
+    fuel: u64,
+    config: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PluginFinding {
+    message: String,
+    /// Variable the finding is about; findings without one are rule violations.
+    #[serde(default)]
+    var: Option<String>,
+}
+
+impl WasmRule {
+    /// Compiles the module of `plugin`, to be registered under `id`.
+    pub fn load(id: &str, plugin: &Plugin) -> Result<Self> {
+        let error = |message: String| Error::Plugin {
+            path: plugin.path.clone(),
+            message,
+        };
+        let bytes = std::fs::read(&plugin.path).map_err(|source| Error::Io {
+            path: plugin.path.clone(),
+            source,
+        })?;
+
+        let mut config = wasmi::Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, bytes).map_err(|e| error(e.to_string()))?;
+        if let Some(import) = module.imports().next() {
+            return Err(error(format!(
+                "the module imports {}::{}, but plugins may not import anything",
+                import.module(),
+                import.name()
+            )));
+        }
+
+        Ok(Self {
+            id: id.to_string(),
+            description: plugin
+                .description
+                .clone()
+                .unwrap_or_else(|| format!("WebAssembly plugin {}", plugin.path.display())),
+            engine,
+            module,
+            fuel: plugin.fuel,
+            config: plugin.config.clone(),
+        })
+    }
+
+    fn run(&self, input: &[u8]) -> Result<Vec<u8>, String> {
+        let trap = |e: wasmi::Error| match e.as_trap_code() {
+            Some(TrapCode::OutOfFuel) => format!("ran out of fuel after {} units", self.fuel),
+            _ => e.to_string(),
+        };
+
+        let limits = StoreLimitsBuilder::new().memory_size(MEMORY_LIMIT).build();
+        let mut store: Store<StoreLimits> = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(self.fuel).map_err(|e| e.to_string())?;
+
+        let instance = Linker::new(&self.engine)
+            .instantiate_and_start(&mut store, &self.module)
+            .map_err(trap)?;
+        let memory = instance.get_memory(&store, "memory").ok_or("the module does not export memory")?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&store, "envcheck_alloc")
+            .map_err(|e| format!("envcheck_alloc: {}", e))?;
+        let check = instance
+            .get_typed_func::<(i32, i32), i64>(&store, "envcheck_check")
+            .map_err(|e| format!("envcheck_check: {}", e))?;
+
+        let len = i32::try_from(input.len()).map_err(|_| "the input is too large".to_string())?;
+        let ptr = alloc.call(&mut store, len).map_err(trap)?;
+        memory
+            .write(&mut store, ptr as u32 as usize, input)
+            .map_err(|e| format!("envcheck_alloc returned an unusable buffer: {}", e))?;
+
+        let packed = check.call(&mut store, (ptr, len)).map_err(trap)? as u64;
+        let (out_ptr, out_len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        if out_len > MEMORY_LIMIT {
+            return Err(format!("envcheck_check returned a {}-byte output", out_len));
+        }
+        let mut output = vec![0; out_len];
+        memory
+            .read(&store, out_ptr, &mut output)
+            .map_err(|e| format!("envcheck_check returned an unusable buffer: {}", e))?;
+        Ok(output)
+    }
+}
+
+impl Rule for WasmRule {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn check(&self, context: &RuleContext<'_>) -> Vec<Finding> {
+        let resource = context.resource;
+        let containers: Vec<_> = resource
+            .containers
+            .iter()
+            .map(|c| json!({ "name": c.name, "env": c.env_vars, "env_from": c.env_from_refs }))
+            .collect();
+        let input = json!({
+            "environment": context.environment,
+            "application": context.application,
+            "resource": {
+                "kind": resource.kind,
+                "name": resource.name,
+                "namespace": resource.effective_namespace(),
+                "labels": resource.labels,
+                "annotations": resource.annotations,
+                "containers": containers,
+            },
+            "vars": context.vars,
+            "required_vars": context.required_vars,
+            "optional_vars": context.optional_vars,
+            "config": self.config,
+        });
+
+        let findings = self
+            .run(input.to_string().as_bytes())
+            .and_then(|output| {
+                serde_json::from_slice::<Vec<PluginFinding>>(&output).map_err(|e| format!("invalid output: {}", e))
+            });
+        match findings {
+            Ok(findings) => findings
+                .into_iter()
+                .map(|finding| match finding.var {
+                    Some(var_name) => Finding::Invalid {
+                        var_name,
+                        message: finding.message,
+                    },
+                    None => Finding::Violation(finding.message),
+                })
+                .collect(),
+            Err(e) => vec![Finding::Violation(format!("Plugin '{}' failed: {}", self.id, e))],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::EnvChecker;
+    use crate::config::Config;
+    use crate::k8s::K8sParser;
+    use crate::rules::RuleRegistry;
+
+    /// A module whose `envcheck_check` loops forever.
+    const SPIN_MODULE: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic, version
+        0x01, 0x0c, 0x02, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7e, // types
+        0x03, 0x03, 0x02, 0x00, 0x01, // functions
+        0x05, 0x03, 0x01, 0x00, 0x01, // memory
+        0x07, 0x2c, 0x03, // exports
+        0x06, b'm', b'e', b'm', b'o', b'r', b'y', 0x02, 0x00,
+        0x0e, b'e', b'n', b'v', b'c', b'h', b'e', b'c', b'k', b'_', b'a', b'l', b'l', b'o', b'c', 0x00, 0x00,
+        0x0e, b'e', b'n', b'v', b'c', b'h', b'e', b'c', b'k', b'_', b'c', b'h', b'e', b'c', b'k', 0x00, 0x01,
+        0x0a, 0x0f, 0x02, // code
+        0x04, 0x00, 0x41, 0x00, 0x0b, // envcheck_alloc: i32.const 0
+        0x08, 0x00, 0x03, 0x40, 0x0c, 0x00, 0x0b, 0x00, 0x0b, // envcheck_check: loop br 0 end unreachable
+    ];
+
+    #[test]
+    fn out_of_fuel_is_a_rule_violation() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("spin.wasm"), SPIN_MODULE).unwrap();
+        let config_path = dir.path().join("envcheck.yaml");
+        std::fs::write(
+            &config_path,
+            "environments:\n  production:\n    required_vars: []\nplugins:\n  spin:\n    path: spin.wasm\n    fuel: 1000\n",
+        )
+        .unwrap();
+        let config = Config::from_path(&config_path).unwrap();
+        let mut rules = RuleRegistry::builtin(&config);
+        rules.register_plugins(&config).unwrap();
+
+        let resources = K8sParser::new()
+            .parse_yaml("apiVersion: apps/v1\nkind: Deployment\nmetadata:\n  name: web\nspec:\n  template:\n    spec:\n      containers: []\n")
+            .unwrap();
+        let results = EnvChecker::new(config).with_rules(rules).check_resources(&resources, "production");
+        let messages: Vec<&str> = results[0].rule_violations.iter().map(|v| v.message.as_str()).collect();
+        assert_eq!(messages, ["Plugin 'spin' failed: ran out of fuel after 1000 units"]);
+    }
+}